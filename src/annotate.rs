@@ -0,0 +1,310 @@
+use crate::cbor::*;
+use crate::decode::DEPTH_LIMIT;
+use crate::error::*;
+use crate::scan::{self, Frames};
+use alloc::string::String;
+use core::fmt::{self, Write};
+use half::f16;
+
+/// Width of the column of raw bytes, indentation included.
+const BYTES_COLUMN: usize = 32;
+
+/// Number of payload bytes printed per line.
+const PAYLOAD_LINE: usize = 16;
+
+/// Annotate every header of the data items of `bytes`, stopping at the
+/// first malformed byte.
+pub(crate) fn annotate(bytes: &[u8]) -> String {
+	let mut annotator = Annotator {
+		bytes,
+		offset: 0,
+		frames: Frames::new(DEPTH_LIMIT),
+		out: String::new(),
+	};
+	if let Err(reason) = annotator.run() {
+		annotator.stop(reason);
+	}
+	annotator.out
+}
+
+/// The reason to stop at a header rejected by `Frames::accept`.
+fn reason(error: Error) -> &'static str {
+	match error {
+		Error::Unexpected(HEADER_BREAK, "map value") => "break after a map key without its value",
+		Error::Unexpected(HEADER_BREAK, _) => "break outside an indefinite container",
+		Error::Unexpected(..) => "expected a definite string chunk of the same type",
+		Error::Unassigned(initial) if initial & 0x1F == SIZE_INFINITE => "indefinite length on a major type without one",
+		Error::Unassigned(_) => "simple value encoded on two bytes below 32",
+		Error::Limit(LimitKind::Depth) => "containers nested too deeply",
+		_ => "map length overflowing",
+	}
+}
+
+struct Annotator<'a> {
+	bytes: &'a [u8],
+	offset: usize,
+	frames: Frames,
+	out: String,
+}
+
+impl<'a> Annotator<'a> {
+	fn run(&mut self) -> Result<(), &'static str> {
+		loop {
+			if self.offset == self.bytes.len() {
+				return if self.frames.is_empty() {
+					Ok(())
+				} else {
+					Err("end of input inside a container")
+				};
+			}
+			self.header()?;
+		}
+	}
+
+	/// Annotate the header at the current offset, and the payload
+	/// of a definite string.
+	fn header(&mut self) -> Result<(), &'static str> {
+		let start = self.offset;
+		let (initial, argument, size) = match scan::header(self.bytes, start) {
+			Ok(Some(header)) => header,
+			Ok(None) => return Err("end of input inside a header"),
+			Err(_) => return Err("reserved additional information"),
+		};
+		let major = initial >> 5;
+		let info = initial & 0x1F;
+		let end = start + size;
+		let header = &self.bytes[start..end];
+		let depth = self.frames.len();
+		let payload = self.frames.accept(initial, argument).map_err(reason)?;
+		self.offset = end;
+		if initial == HEADER_BREAK {
+			self.line(start, depth - 1, header, format_args!("break"));
+			return Ok(());
+		}
+
+		match (major, info) {
+			(MAJOR_POSITIVE, _) => self.line(start, depth, header, format_args!("unsigned({})", argument)),
+			(MAJOR_NEGATIVE, _) => {
+				self.line(start, depth, header, format_args!("negative({})", -1 - argument as i128))
+			}
+			(MAJOR_BYTE, SIZE_INFINITE) | (MAJOR_TEXT, SIZE_INFINITE) => {
+				let name = if major == MAJOR_BYTE { "bytes" } else { "text" };
+				self.line(start, depth, header, format_args!("{}(*)", name));
+			}
+			(MAJOR_BYTE, _) | (MAJOR_TEXT, _) => {
+				let name = if major == MAJOR_BYTE { "bytes" } else { "text" };
+				self.line(start, depth, header, format_args!("{}({})", name, argument));
+				self.payload(major, payload, depth + 1)?;
+			}
+			(MAJOR_ARRAY, SIZE_INFINITE) | (MAJOR_MAP, SIZE_INFINITE) => {
+				let name = if major == MAJOR_ARRAY { "array" } else { "map" };
+				self.line(start, depth, header, format_args!("{}(*)", name));
+			}
+			(MAJOR_ARRAY, _) => self.line(start, depth, header, format_args!("array({})", argument)),
+			(MAJOR_MAP, _) => self.line(start, depth, header, format_args!("map({})", argument)),
+			(MAJOR_TAG, _) => self.line(start, depth, header, format_args!("tag({})", argument)),
+			(_, SIZE_16) => {
+				let value = f16::from_bits(argument as u16).to_f64();
+				self.line(start, depth, header, format_args!("float16({:?})", value));
+			}
+			(_, SIZE_32) => {
+				let value = f32::from_bits(argument as u32);
+				self.line(start, depth, header, format_args!("float32({:?})", value));
+			}
+			(_, SIZE_64) => {
+				let value = f64::from_bits(argument);
+				self.line(start, depth, header, format_args!("float64({:?})", value));
+			}
+			_ => match argument as u8 {
+				SIMPLE_FALSE => self.line(start, depth, header, format_args!("false")),
+				SIMPLE_TRUE => self.line(start, depth, header, format_args!("true")),
+				SIMPLE_NULL => self.line(start, depth, header, format_args!("null")),
+				SIMPLE_UNDEFINED => self.line(start, depth, header, format_args!("undefined")),
+				value => self.line(start, depth, header, format_args!("simple({})", value)),
+			},
+		}
+		Ok(())
+	}
+
+	/// Annotate the payload of a definite string, a few bytes per line.
+	fn payload(&mut self, major: u8, len: u64, depth: usize) -> Result<(), &'static str> {
+		let start = self.offset;
+		let available = (self.bytes.len() - start) as u64;
+		if len > available {
+			return Err("end of input inside a string");
+		}
+		let payload = &self.bytes[start..start + len as usize];
+		self.offset += payload.len();
+		for (index, chunk) in payload.chunks(PAYLOAD_LINE).enumerate() {
+			let offset = start + index * PAYLOAD_LINE;
+			match (major, index) {
+				(MAJOR_TEXT, 0) => match core::str::from_utf8(payload) {
+					Ok(text) => self.line(offset, depth, chunk, format_args!("{:?}", text)),
+					Err(_) => self.line(offset, depth, chunk, format_args!("invalid UTF-8")),
+				},
+				_ => self.line(offset, depth, chunk, format_args!("")),
+			}
+		}
+		Ok(())
+	}
+
+	/// Write `offset`, then `bytes` and `comment` indented by `depth`.
+	fn line(&mut self, offset: usize, depth: usize, bytes: &[u8], comment: fmt::Arguments) {
+		let start = self.out.len();
+		// Writing to a `String` cannot fail.
+		let _ = write!(self.out, "{:08x}  {:width$}", offset, "", width = depth * 2);
+		for (index, byte) in bytes.iter().enumerate() {
+			let separator = if index == 0 { "" } else { " " };
+			let _ = write!(self.out, "{}{:02x}", separator, byte);
+		}
+		// The comment is dropped again, with its padding, if it is empty.
+		let end = self.out.len();
+		let column = start + 10 + BYTES_COLUMN;
+		let padding = column.saturating_sub(end).max(1);
+		let _ = write!(self.out, "{:padding$}# {:width$}", "", "", padding = padding, width = depth * 2);
+		let before = self.out.len();
+		let _ = self.out.write_fmt(comment);
+		if self.out.len() == before {
+			self.out.truncate(end);
+		}
+		self.out.push('\n');
+	}
+
+	/// Mark where the annotation stopped and why.
+	fn stop(&mut self, reason: &str) {
+		let left = self.bytes.len() - self.offset;
+		let _ = writeln!(
+			self.out,
+			"{:08x}  ^ stopped, {} ({} byte{} left)",
+			self.offset,
+			reason,
+			left,
+			if left == 1 { "" } else { "s" }
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nested_items() {
+		// {"a": [1, -1], "bc": 1(1.5_3)}
+		let bytes = [0xA2, 0x61, b'a', 0x82, 0x01, 0x20, 0x62, b'b', b'c', 0xC1, 0xFB, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0];
+		assert_eq!(
+			annotate(&bytes),
+			concat!(
+				"00000000  a2                              # map(2)\n",
+				"00000001    61                            #   text(1)\n",
+				"00000002      61                          #     \"a\"\n",
+				"00000003    82                            #   array(2)\n",
+				"00000004      01                          #     unsigned(1)\n",
+				"00000005      20                          #     negative(-1)\n",
+				"00000006    62                            #   text(2)\n",
+				"00000007      62 63                       #     \"bc\"\n",
+				"00000009    c1                            #   tag(1)\n",
+				"0000000a      fb 3f f8 00 00 00 00 00 00  #     float64(1.5)\n",
+			)
+		);
+	}
+
+	#[test]
+	fn indefinite_items() {
+		let bytes = [
+			0x5F, 0x41, 0x01, 0xFF, 0x9F, 0xF4, 0xF5, 0xF6, 0xF7, 0xF0, 0xF8, 0xFF, 0xF9, 0x3E, 0x00, 0xFA, 0x3F, 0xC0, 0x00,
+			0x00, 0xFF,
+		];
+		assert_eq!(
+			annotate(&bytes),
+			concat!(
+				"00000000  5f                              # bytes(*)\n",
+				"00000001    41                            #   bytes(1)\n",
+				"00000002      01\n",
+				"00000003  ff                              # break\n",
+				"00000004  9f                              # array(*)\n",
+				"00000005    f4                            #   false\n",
+				"00000006    f5                            #   true\n",
+				"00000007    f6                            #   null\n",
+				"00000008    f7                            #   undefined\n",
+				"00000009    f0                            #   simple(16)\n",
+				"0000000a    f8 ff                         #   simple(255)\n",
+				"0000000c    f9 3e 00                      #   float16(1.5)\n",
+				"0000000f    fa 3f c0 00 00                #   float32(1.5)\n",
+				"00000014  ff                              # break\n",
+			)
+		);
+	}
+
+	#[test]
+	fn long_payloads() {
+		let mut bytes = alloc::vec![0x58, 0x14];
+		bytes.extend(0..0x14);
+		bytes.extend_from_slice(&[0x62, 0xFF, 0xFE]);
+		assert_eq!(
+			annotate(&bytes),
+			concat!(
+				"00000000  58 14                           # bytes(20)\n",
+				"00000002    00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n",
+				"00000012    10 11 12 13\n",
+				"00000016  62                              # text(2)\n",
+				"00000017    ff fe                         #   invalid UTF-8\n",
+			)
+		);
+		assert_eq!(annotate(&[]), "");
+	}
+
+	#[test]
+	fn stopped() {
+		assert_eq!(
+			annotate(&[0x82, 0x01]),
+			concat!(
+				"00000000  82                              # array(2)\n",
+				"00000001    01                            #   unsigned(1)\n",
+				"00000002  ^ stopped, end of input inside a container (0 bytes left)\n",
+			)
+		);
+		assert_eq!(
+			annotate(&[0x7F, 0x41, 0x00]),
+			concat!(
+				"00000000  7f                              # text(*)\n",
+				"00000001  ^ stopped, expected a definite string chunk of the same type (2 bytes left)\n",
+			)
+		);
+		assert_eq!(
+			annotate(&[0x63, 0x61]),
+			concat!(
+				"00000000  63                              # text(3)\n",
+				"00000001  ^ stopped, end of input inside a string (1 byte left)\n",
+			)
+		);
+		assert_eq!(annotate(&[0x19, 0x01]), "00000000  ^ stopped, end of input inside a header (2 bytes left)\n");
+		assert_eq!(annotate(&[0x1C]), "00000000  ^ stopped, reserved additional information (1 byte left)\n");
+		assert_eq!(annotate(&[0xFF]), "00000000  ^ stopped, break outside an indefinite container (1 byte left)\n");
+		assert_eq!(
+			annotate(&[0xF8, 0x10]),
+			"00000000  ^ stopped, simple value encoded on two bytes below 32 (2 bytes left)\n"
+		);
+		assert_eq!(
+			annotate(&[0xDF, 0x00]),
+			"00000000  ^ stopped, indefinite length on a major type without one (2 bytes left)\n"
+		);
+		assert_eq!(
+			annotate(&[0xBF, 0x01, 0xFF]),
+			concat!(
+				"00000000  bf                              # map(*)\n",
+				"00000001    01                            #   unsigned(1)\n",
+				"00000002  ^ stopped, break after a map key without its value (1 byte left)\n",
+			)
+		);
+	}
+
+	#[test]
+	fn deep_input() {
+		let bytes = alloc::vec![0x81; 40_000];
+		let out = annotate(&bytes);
+		assert_eq!(out.lines().count(), DEPTH_LIMIT + 1);
+		let last = alloc::format!("{:08x}  ^ stopped, containers nested too deeply ({} bytes left)\n", DEPTH_LIMIT, 40_000 - DEPTH_LIMIT);
+		assert!(out.ends_with(&last));
+	}
+}
@@ -0,0 +1,104 @@
+use crate::error::*;
+use crate::scan::{Scan, Scanner};
+use crate::Result;
+use alloc::vec::Vec;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Read exactly one data item from `reader` and return its bytes.
+///
+/// The item is scanned while it is read, so nothing is read past it.
+/// When `limit` is set, an item bigger than `limit` bytes is rejected
+/// with `Error::Limit(LimitKind::ReadBuffer)`.
+pub async fn read_item<R>(reader: &mut R, limit: Option<usize>) -> Result<Vec<u8>>
+where
+	R: AsyncRead + Unpin,
+{
+	let mut scanner = Scanner::new();
+	let mut bytes = Vec::new();
+	loop {
+		match scanner.scan(&bytes)? {
+			Scan::Complete(_) => return Ok(bytes),
+			Scan::NeedMore(more) => {
+				match (bytes.len().checked_add(more), limit) {
+					(None, _) => return Err(Error::Limit(LimitKind::Index)),
+					(Some(end), Some(limit)) if end > limit => {
+						return Err(Error::Limit(LimitKind::ReadBuffer))
+					}
+					_ => {}
+				}
+				// The buffer grows as bytes arrive, not as announced by the input.
+				if (&mut *reader).take(more as u64).read_to_end(&mut bytes).await? < more {
+					return Err(Error::Eof);
+				}
+			}
+		}
+	}
+}
+
+/// Write `bytes` to `writer` and return the number of bytes written.
+pub async fn write_item<W>(writer: &mut W, bytes: &[u8]) -> Result<usize>
+where
+	W: AsyncWrite + Unpin,
+{
+	writer.write_all(bytes).await?;
+	Ok(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::io::duplex;
+
+	/// [_ h'0102', {"a": 1(-1)}] then 2
+	const SEQUENCE: [u8; 12] = [0x9F, 0x42, 0x01, 0x02, 0xA1, 0x61, 0x61, 0xC1, 0x20, 0xFF, 0x02, 0x03];
+
+	#[tokio::test]
+	async fn items_through_a_duplex() {
+		let (mut client, mut server) = duplex(4);
+		let writer = tokio::spawn(async move {
+			for byte in SEQUENCE.chunks(1) {
+				write_item(&mut client, byte).await.unwrap();
+			}
+		});
+		assert_eq!(read_item(&mut server, None).await.unwrap(), &SEQUENCE[..10]);
+		assert_eq!(read_item(&mut server, None).await.unwrap(), [0x02]);
+		assert_eq!(read_item(&mut server, Some(1)).await.unwrap(), [0x03]);
+		writer.await.unwrap();
+		assert!(matches!(read_item(&mut server, None).await, Err(Error::Eof)));
+	}
+
+	#[tokio::test]
+	async fn truncated_items() {
+		let (mut client, mut server) = duplex(64);
+		client.write_all(&SEQUENCE[..6]).await.unwrap();
+		drop(client);
+		assert!(matches!(read_item(&mut server, None).await, Err(Error::Eof)));
+	}
+
+	#[tokio::test]
+	async fn limits() {
+		let (mut client, mut server) = duplex(64);
+		client.write_all(&SEQUENCE).await.unwrap();
+		assert!(matches!(read_item(&mut server, Some(9)).await, Err(Error::Limit(LimitKind::ReadBuffer))));
+
+		// A huge announced length is rejected before any allocation.
+		let (mut client, mut server) = duplex(64);
+		client.write_all(&[0x5B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).await.unwrap();
+		assert!(matches!(read_item(&mut server, Some(1024)).await, Err(Error::Limit(LimitKind::ReadBuffer))));
+	}
+
+	#[tokio::test]
+	async fn huge_length_without_limit() {
+		let (mut client, mut server) = duplex(64);
+		client.write_all(&[0x5B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]).await.unwrap();
+		drop(client);
+		assert!(matches!(read_item(&mut server, None).await, Err(Error::Eof)));
+	}
+
+	#[tokio::test]
+	async fn malformed_items() {
+		let (mut client, mut server) = duplex(64);
+		client.write_all(&[0x82, 0x01, 0xFF]).await.unwrap();
+		assert!(matches!(read_item(&mut server, None).await, Err(Error::Unexpected(0xFF, _))));
+	}
+}
@@ -0,0 +1,180 @@
+//! Convert between CBOR and JSON on the standard input and output.
+//!
+//! ```text
+//! cbor to-json [--sequence] [--pretty]
+//! cbor from-json [--sequence]
+//! ```
+//!
+//! With `--sequence`, the CBOR side is a sequence of data items (RFC 8742)
+//! and the JSON side holds one JSON text per item, one per line on output.
+//!
+//! JSON to CBOR is lossless: integers become major types 0 and 1, other
+//! numbers double precision floats, and objects keep the order of their keys.
+//!
+//! CBOR to JSON follows RFC 8949 section 6.1 and is lossy:
+//!
+//! - negative integers below `i64::MIN` and floats become JSON numbers,
+//!   NaN and infinities become `null`;
+//! - byte strings become base64url strings without padding, base64 with
+//!   padding inside tag 22 and lowercase base16 inside tag 23;
+//! - bignums (tags 2 and 3) become base64url strings of their magnitude,
+//!   prefixed with `~` when negative;
+//! - any other tag is dropped, keeping its content;
+//! - `undefined` and the other simple values become `null`;
+//! - map keys that are not text strings are converted to JSON, their JSON
+//!   text being used as the key, the last one winning on duplicates.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use orandja_cbor::decode::Decoder;
+use orandja_cbor::error::Error;
+use orandja_cbor::read::SliceReader;
+use orandja_cbor::value::Value;
+use serde_json::{Map, Number};
+use std::convert::TryFrom;
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
+
+const USAGE: &str = "usage: cbor to-json [--sequence] [--pretty]\n       cbor from-json [--sequence]";
+
+/// How byte strings are written as JSON strings.
+#[derive(Clone, Copy)]
+enum Bytes {
+	Base64Url,
+	Base64,
+	Base16,
+}
+
+fn main() {
+	let mut args = std::env::args().skip(1);
+	let mode = args.next();
+	let mut sequence = false;
+	let mut pretty = false;
+	for arg in args {
+		match arg.as_str() {
+			"--sequence" => sequence = true,
+			"--pretty" => pretty = true,
+			_ => usage(),
+		}
+	}
+	let result = match mode.as_deref() {
+		Some("to-json") => to_json(sequence, pretty),
+		Some("from-json") if !pretty => from_json(sequence),
+		Some("-h") | Some("--help") => {
+			println!("{}", USAGE);
+			return;
+		}
+		_ => usage(),
+	};
+	if let Err(error) = result {
+		eprintln!("cbor: {}", error);
+		process::exit(1);
+	}
+}
+
+fn usage() -> ! {
+	eprintln!("{}", USAGE);
+	process::exit(2)
+}
+
+fn to_json(sequence: bool, pretty: bool) -> Result<(), Box<dyn std::error::Error>> {
+	let mut input = Vec::new();
+	io::stdin().lock().read_to_end(&mut input)?;
+	let stdout = io::stdout();
+	let mut output = BufWriter::new(stdout.lock());
+	let mut decoder = Decoder::new(SliceReader::new(&input));
+	loop {
+		if sequence {
+			match decoder.peek_initial() {
+				Err(Error::Eof) => break,
+				Err(error) => return Err(at(&decoder, error)),
+				Ok(_) => {}
+			}
+		}
+		let value = Value::decode(&mut decoder).map_err(|error| at(&decoder, error))?;
+		let json = convert(&value, Bytes::Base64Url);
+		if pretty {
+			serde_json::to_writer_pretty(&mut output, &json)?;
+		} else {
+			serde_json::to_writer(&mut output, &json)?;
+		}
+		writeln!(output)?;
+		if !sequence {
+			decoder.end().map_err(|error| at(&decoder, error))?;
+			break;
+		}
+	}
+	output.flush()?;
+	Ok(())
+}
+
+fn from_json(sequence: bool) -> Result<(), Box<dyn std::error::Error>> {
+	let stdin = io::stdin();
+	let stdout = io::stdout();
+	let mut output = BufWriter::new(stdout.lock());
+	if sequence {
+		for value in serde_json::Deserializer::from_reader(stdin.lock()).into_iter::<serde_json::Value>() {
+			orandja_cbor::to_writer(&mut output, &value?)?;
+		}
+	} else {
+		let value: serde_json::Value = serde_json::from_reader(stdin.lock())?;
+		orandja_cbor::to_writer(&mut output, &value)?;
+	}
+	output.flush()?;
+	Ok(())
+}
+
+fn at(decoder: &Decoder<SliceReader>, error: Error) -> Box<dyn std::error::Error> {
+	format!("at byte {}: {}", decoder.offset(), error).into()
+}
+
+fn convert(value: &Value, bytes: Bytes) -> serde_json::Value {
+	match value {
+		Value::UInt(value) => (*value).into(),
+		Value::NegInt(value) => match i64::try_from(*value) {
+			Ok(value) => (-1 - value).into(),
+			Err(_) => float(-1.0 - *value as f64),
+		},
+		Value::Bytes(value) => encode_bytes(value, bytes).into(),
+		Value::Text(value) => value.as_str().into(),
+		Value::Array(items) => items.iter().map(|item| convert(item, bytes)).collect(),
+		Value::Map(pairs) => {
+			let mut map = Map::new();
+			for (key, value) in pairs {
+				let key = match key {
+					Value::Text(key) => key.clone(),
+					key => convert(key, bytes).to_string(),
+				};
+				map.insert(key, convert(value, bytes));
+			}
+			serde_json::Value::Object(map)
+		}
+		Value::Tag(2, content) => match &**content {
+			Value::Bytes(value) => URL_SAFE_NO_PAD.encode(value).into(),
+			content => convert(content, bytes),
+		},
+		Value::Tag(3, content) => match &**content {
+			Value::Bytes(value) => format!("~{}", URL_SAFE_NO_PAD.encode(value)).into(),
+			content => convert(content, bytes),
+		},
+		Value::Tag(21, content) => convert(content, Bytes::Base64Url),
+		Value::Tag(22, content) => convert(content, Bytes::Base64),
+		Value::Tag(23, content) => convert(content, Bytes::Base16),
+		Value::Tag(_, content) => convert(content, bytes),
+		Value::Bool(value) => (*value).into(),
+		Value::Null | Value::Undefined | Value::Simple(_) => serde_json::Value::Null,
+		Value::Float(value) => float(*value),
+	}
+}
+
+fn float(value: f64) -> serde_json::Value {
+	Number::from_f64(value).map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+fn encode_bytes(value: &[u8], bytes: Bytes) -> String {
+	match bytes {
+		Bytes::Base64Url => URL_SAFE_NO_PAD.encode(value),
+		Bytes::Base64 => STANDARD.encode(value),
+		Bytes::Base16 => value.iter().map(|byte| format!("{:02x}", byte)).collect(),
+	}
+}
@@ -0,0 +1,184 @@
+use alloc::vec::Vec;
+use bytes::Bytes;
+use serde::{de, ser};
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+
+/// Name of the newtype used to tell the `Deserializer` that a shared
+/// `Bytes` is expected.
+pub(crate) const TOKEN: &str = "$__orandja_cbor_private_SharedBytes";
+
+/// Visit `bytes`, handing them over as they are when `visitor`
+/// is the one of `shared`, and as a borrowed slice otherwise.
+pub(crate) fn visit_shared<'de, V, E>(bytes: Bytes, visitor: V) -> Result<V::Value, E>
+where
+	V: de::Visitor<'de>,
+	E: de::Error,
+{
+	if type_id::<V>() == TypeId::of::<SharedVisitor>() {
+		let bytes = ManuallyDrop::new(bytes);
+		// SAFETY: `V` is `SharedVisitor`, which has no lifetime, so its
+		// value is `Bytes`. The original is not dropped.
+		return Ok(unsafe { mem::transmute_copy::<Bytes, V::Value>(&bytes) });
+	}
+	visitor.visit_bytes(&bytes)
+}
+
+/// The `TypeId` of `T` with its lifetimes erased, for types that
+/// are not `'static`.
+fn type_id<T: ?Sized>() -> TypeId {
+	trait NonStaticAny {
+		fn type_id(&self) -> TypeId
+		where
+			Self: 'static;
+	}
+
+	impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+		fn type_id(&self) -> TypeId
+		where
+			Self: 'static,
+		{
+			TypeId::of::<T>()
+		}
+	}
+
+	let phantom = PhantomData::<T>;
+	// SAFETY: only the lifetime of the trait object is extended, and
+	// `type_id` does not keep any reference.
+	let any = unsafe { mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom) };
+	any.type_id()
+}
+
+/// Serde helpers for `bytes::Bytes` fields, to be used with
+/// `#[serde(with = "orandja_cbor::buf::shared")]`.
+///
+/// When decoded by `from_bytes` or from a `read::BytesReader` over a
+/// `Bytes`, the fields share the allocation of the input instead of
+/// being copied.
+pub mod shared {
+	use super::*;
+
+	pub fn serialize<S>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: ser::Serializer,
+	{
+		serializer.serialize_bytes(value)
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+	where
+		D: de::Deserializer<'de>,
+	{
+		deserializer.deserialize_newtype_struct(TOKEN, SharedVisitor)
+	}
+}
+
+struct SharedVisitor;
+
+impl<'de> de::Visitor<'de> for SharedVisitor {
+	type Value = Bytes;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a byte string")
+	}
+
+	fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: de::Deserializer<'de>,
+	{
+		deserializer.deserialize_bytes(self)
+	}
+
+	fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+		Ok(Bytes::copy_from_slice(bytes))
+	}
+
+	fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+		Ok(Bytes::from(bytes))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::deserialize::Deserializer;
+	use crate::read::BytesReader;
+	use bytes::Buf;
+	use serde_derive::Deserialize;
+
+	#[derive(Deserialize)]
+	struct Packet {
+		id: u8,
+		#[serde(with = "shared")]
+		data: Bytes,
+	}
+
+	/// {"id": 7, "data": h'010203'}
+	const PACKET: [u8; 14] = [0xA2, 0x62, b'i', b'd', 0x07, 0x64, b'd', b'a', b't', b'a', 0x43, 0x01, 0x02, 0x03];
+
+	fn shares(input: &Bytes, bytes: &Bytes) -> bool {
+		input.as_ptr_range().contains(&bytes.as_ptr())
+	}
+
+	#[test]
+	fn from_bytes_shares_the_input() {
+		let input = Bytes::from(PACKET.to_vec());
+		let packet: Packet = crate::from_bytes(&input).unwrap();
+		assert_eq!((packet.id, &packet.data[..]), (7, &[1, 2, 3][..]));
+		assert!(shares(&input, &packet.data));
+	}
+
+	#[test]
+	fn bytes_reader_shares_the_buffer() {
+		let input = Bytes::from(PACKET.to_vec());
+		let mut deserializer = Deserializer::new(BytesReader::new(input.clone()));
+		let packet: Packet = de::Deserialize::deserialize(&mut deserializer).unwrap();
+		assert_eq!((packet.id, &packet.data[..]), (7, &[1, 2, 3][..]));
+		assert!(shares(&input, &packet.data));
+	}
+
+	#[test]
+	fn other_inputs_are_copied() {
+		let packet: Packet = crate::from_slice(&PACKET).unwrap();
+		assert_eq!(packet.data, [1, 2, 3][..]);
+
+		// A non-contiguous buffer split in the middle of the byte string.
+		let chain = (&PACKET[..12]).chain(&PACKET[12..]);
+		let mut deserializer = Deserializer::new(BytesReader::new(chain));
+		let packet: Packet = de::Deserialize::deserialize(&mut deserializer).unwrap();
+		assert_eq!(packet.data, [1, 2, 3][..]);
+	}
+
+	#[test]
+	fn other_types_are_rejected() {
+		let mut packet = PACKET;
+		packet[10] = 0x01;
+		assert!(crate::from_slice::<Packet>(&packet[..11]).is_err());
+	}
+
+	#[test]
+	fn other_visitors_borrow() {
+		struct Len;
+
+		impl<'de> de::Visitor<'de> for Len {
+			type Value = usize;
+
+			fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				write!(f, "a byte string")
+			}
+
+			fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<usize, E> {
+				Ok(bytes.len())
+			}
+		}
+
+		let input = Bytes::from_static(&[0x43, 0x01, 0x02, 0x03]);
+		let mut deserializer = Deserializer::new(BytesReader::new(input));
+		let len = de::Deserializer::deserialize_newtype_struct(&mut deserializer, TOKEN, Len).unwrap();
+		assert_eq!(len, 3);
+		assert_ne!(type_id::<Len>(), type_id::<SharedVisitor>());
+		assert_eq!(type_id::<&'static str>(), type_id::<&str>());
+	}
+}
@@ -62,11 +62,17 @@ pub const HEADER_TAG_16: u8 = MAJOR_TAG << 5 | SIZE_16;
 pub const HEADER_TAG_32: u8 = MAJOR_TAG << 5 | SIZE_32;
 pub const HEADER_TAG_64: u8 = MAJOR_TAG << 5 | SIZE_64;
 
-pub const HEADER_FALSE: u8 = MAJOR_PRIMITIVE << 5 | 20;
-pub const HEADER_TRUE: u8 = MAJOR_PRIMITIVE << 5 | 21;
-pub const HEADER_NULL: u8 = MAJOR_PRIMITIVE << 5 | 22;
-pub const HEADER_UNDEFINED: u8 = MAJOR_PRIMITIVE << 5 | 23;
-pub const HEADER_BREAK: u8 = MAJOR_PRIMITIVE << 5 | 31;
+pub const SIMPLE_FALSE: u8 = 20;
+pub const SIMPLE_TRUE: u8 = 21;
+pub const SIMPLE_NULL: u8 = 22;
+pub const SIMPLE_UNDEFINED: u8 = 23;
+
+pub const HEADER_FALSE: u8 = MAJOR_PRIMITIVE << 5 | SIMPLE_FALSE;
+pub const HEADER_TRUE: u8 = MAJOR_PRIMITIVE << 5 | SIMPLE_TRUE;
+pub const HEADER_NULL: u8 = MAJOR_PRIMITIVE << 5 | SIMPLE_NULL;
+pub const HEADER_UNDEFINED: u8 = MAJOR_PRIMITIVE << 5 | SIMPLE_UNDEFINED;
+pub const HEADER_SIMPLE_8: u8 = MAJOR_PRIMITIVE << 5 | SIZE_8;
+pub const HEADER_BREAK: u8 = MAJOR_PRIMITIVE << 5 | SIZE_INFINITE;
 
 pub const HEADER_FLOAT_16: u8 = MAJOR_PRIMITIVE << 5 | SIZE_16;
 pub const HEADER_FLOAT_32: u8 = MAJOR_PRIMITIVE << 5 | SIZE_32;
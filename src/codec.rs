@@ -0,0 +1,191 @@
+use crate::error::*;
+use crate::scan::{Scan, Scanner};
+use crate::serialize::Serializer;
+use crate::write::BufMutWriter;
+use crate::Result;
+use bytes::BytesMut;
+use serde::{de, ser};
+use core::fmt;
+use core::marker::PhantomData;
+use tokio_util::codec;
+
+/// A `tokio_util` codec splitting a byte stream into CBOR data items.
+///
+/// Data items are self-delimiting, frames are simply concatenated
+/// without any length prefix.
+pub struct CborCodec<T> {
+	max_frame: usize,
+	scanner: Scanner,
+	output: PhantomData<fn() -> T>,
+}
+
+impl<T> CborCodec<T> {
+	pub fn new() -> Self {
+		CborCodec::with_max_frame(usize::MAX)
+	}
+
+	/// Create a codec rejecting the frames bigger than `max_frame` bytes
+	/// with `Error::Limit(LimitKind::Frame)`.
+	pub fn with_max_frame(max_frame: usize) -> Self {
+		CborCodec {
+			max_frame,
+			scanner: Scanner::new(),
+			output: PhantomData,
+		}
+	}
+
+	pub fn max_frame(&self) -> usize {
+		self.max_frame
+	}
+}
+
+impl<T> Default for CborCodec<T> {
+	fn default() -> Self {
+		CborCodec::new()
+	}
+}
+
+impl<T> Clone for CborCodec<T> {
+	fn clone(&self) -> Self {
+		CborCodec::with_max_frame(self.max_frame)
+	}
+}
+
+impl<T> fmt::Debug for CborCodec<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("CborCodec")
+			.field("max_frame", &self.max_frame)
+			.finish()
+	}
+}
+
+impl<T> codec::Decoder for CborCodec<T>
+where
+	T: de::DeserializeOwned,
+{
+	type Item = T;
+	type Error = Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>> {
+		// The buffer is left untouched until a whole data item is available,
+		// the scanner resumes where it stopped on the next call.
+		match self.scanner.scan(&src[..]) {
+			Ok(Scan::Complete(len)) if len > self.max_frame => Err(Error::Limit(LimitKind::Frame)),
+			Ok(Scan::Complete(len)) => {
+				let frame = src.split_to(len).freeze();
+				crate::from_bytes(&frame).map(Some)
+			}
+			Ok(Scan::NeedMore(more)) => match src.len().checked_add(more) {
+				Some(len) if len <= self.max_frame => Ok(None),
+				_ => Err(Error::Limit(LimitKind::Frame)),
+			},
+			Err(error) => {
+				self.scanner.reset();
+				Err(error)
+			}
+		}
+	}
+}
+
+impl<T> codec::Encoder<T> for CborCodec<T>
+where
+	T: ser::Serialize,
+{
+	type Error = Error;
+
+	fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<()> {
+		let start = dst.len();
+		item.serialize(&mut Serializer::new(BufMutWriter::new(&mut *dst)))?;
+		if dst.len() - start > self.max_frame {
+			dst.truncate(start);
+			return Err(Error::Limit(LimitKind::Frame));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::String;
+	use alloc::vec::Vec;
+	use tokio_util::codec::{Decoder, Encoder};
+
+	#[test]
+	fn decode_byte_by_byte() {
+		// "abc" then [1, 2]
+		let stream = [0x63, 0x61, 0x62, 0x63, 0x82, 0x01, 0x02];
+		let mut codec = CborCodec::<String>::new();
+		let mut src = BytesMut::new();
+		let mut frames = Vec::new();
+		for byte in &stream[..4] {
+			src.extend_from_slice(&[*byte]);
+			if let Some(frame) = codec.decode(&mut src).unwrap() {
+				frames.push(frame);
+			}
+		}
+		assert_eq!(frames, ["abc"]);
+		assert!(src.is_empty());
+
+		let mut codec = CborCodec::<Vec<u8>>::new();
+		src.extend_from_slice(&stream[4..6]);
+		assert_eq!(codec.decode(&mut src).unwrap(), None);
+		assert_eq!(src.len(), 2);
+		src.extend_from_slice(&stream[6..]);
+		assert_eq!(codec.decode(&mut src).unwrap(), Some(alloc::vec![1, 2]));
+	}
+
+	#[test]
+	fn decode_max_frame() {
+		// A frame of exactly `max_frame` bytes passes.
+		let mut codec = CborCodec::<String>::with_max_frame(4);
+		assert_eq!(codec.max_frame(), 4);
+		let mut src = BytesMut::from(&[0x63, 0x61, 0x62, 0x63][..]);
+		assert_eq!(codec.decode(&mut src).unwrap().as_deref(), Some("abc"));
+
+		// A bigger one is rejected as soon as its header is known.
+		let mut src = BytesMut::from(&[0x64][..]);
+		assert!(matches!(codec.decode(&mut src), Err(Error::Limit(LimitKind::Frame))));
+
+		// An indefinite one when it grows past the limit.
+		let mut codec = CborCodec::<String>::with_max_frame(4);
+		let mut src = BytesMut::from(&[0x7F, 0x61, 0x61][..]);
+		assert_eq!(codec.decode(&mut src).unwrap(), None);
+		src.extend_from_slice(&[0x61]);
+		assert!(matches!(codec.decode(&mut src), Err(Error::Limit(LimitKind::Frame))));
+	}
+
+	#[test]
+	fn decode_malformed_resets() {
+		let mut codec = CborCodec::<u8>::new();
+		let mut src = BytesMut::from(&[0x82, 0xFF][..]);
+		assert!(matches!(codec.decode(&mut src), Err(Error::Unexpected(0xFF, _))));
+		let mut src = BytesMut::from(&[0x07][..]);
+		assert_eq!(codec.decode(&mut src).unwrap(), Some(7));
+	}
+
+	#[test]
+	fn decode_depth_is_limited() {
+		let mut codec = CborCodec::<u8>::new();
+		let mut src = BytesMut::from(&[0x81; 1000][..]);
+		assert!(matches!(codec.decode(&mut src), Err(Error::Limit(LimitKind::Depth))));
+	}
+
+	#[test]
+	fn encode_max_frame() {
+		let mut codec = CborCodec::<&str>::with_max_frame(4);
+		let mut dst = BytesMut::new();
+		codec.encode("abc", &mut dst).unwrap();
+		assert_eq!(&dst[..], [0x63, 0x61, 0x62, 0x63]);
+		assert!(matches!(codec.encode("abcd", &mut dst), Err(Error::Limit(LimitKind::Frame))));
+		// The rejected frame is not left half written.
+		assert_eq!(dst.len(), 4);
+	}
+
+	#[test]
+	fn clone_keeps_max_frame() {
+		let codec = CborCodec::<u8>::with_max_frame(10);
+		assert_eq!(codec.clone().max_frame(), 10);
+		assert_eq!(CborCodec::<u8>::default().max_frame(), usize::MAX);
+	}
+}
@@ -0,0 +1,589 @@
+use crate::cbor::*;
+use crate::error::*;
+use crate::read::*;
+use crate::scan::{argument_size, Frames};
+use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// A decoded header: the major type of a data item together with its argument.
+/// `None` stands for an indefinite length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Header {
+	Positive(u64),
+	Negative(u64),
+	Bytes(Option<u64>),
+	Text(Option<u64>),
+	Array(Option<u64>),
+	Map(Option<u64>),
+	Tag(u64),
+	Simple(u8),
+	Float(f64),
+	Break,
+}
+
+/// An event produced by the pull parser.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<'a> {
+	/// An unsigned integer.
+	UInt(u64),
+	/// A negative integer, the value being `-1 - n`.
+	NegInt(u64),
+	/// A definite byte string.
+	Bytes(&'a [u8]),
+	/// A definite text string.
+	Text(&'a str),
+	/// The start of an array of `n` elements (or indefinite).
+	Array(Option<u64>),
+	/// The start of a map of `n` pairs (or indefinite).
+	Map(Option<u64>),
+	/// A tag applied to the next data item.
+	Tag(u64),
+	/// A simple value (false, true, null and undefined included).
+	Simple(u8),
+	/// A floating point of any precision.
+	Float(f64),
+	/// The end of an indefinite container.
+	Break,
+}
+
+/// Where the bytes of a skipped data item are captured.
+#[cfg(feature = "alloc")]
+pub(crate) type Capture = Vec<u8>;
+
+/// Nothing can be captured without the `alloc` feature.
+#[cfg(not(feature = "alloc"))]
+pub(crate) enum Capture {}
+
+#[cfg(not(feature = "alloc"))]
+impl Capture {
+	fn push(&mut self, _: u8) {
+		match *self {}
+	}
+
+	fn extend_from_slice(&mut self, _: &[u8]) {
+		match *self {}
+	}
+}
+
+/// Default maximum nesting of containers and tags of a `Decoder`.
+pub const DEPTH_LIMIT: usize = 128;
+
+/// A pull parser reading headers out of a `Reader`.
+pub struct Decoder<R> {
+	reader: R,
+	/// Number of bytes read so far.
+	offset: usize,
+	start: usize,
+	initial: u8,
+	argument: u64,
+	peeked_initial: bool,
+	peeked_header: Option<Header>,
+	depth: usize,
+	depth_limit: usize,
+}
+
+impl<'r, R: Reader<'r>> Decoder<R> {
+	pub fn new(reader: R) -> Self {
+		Decoder {
+			reader,
+			offset: 0,
+			start: 0,
+			initial: 0,
+			argument: 0,
+			peeked_initial: false,
+			peeked_header: None,
+			depth: 0,
+			depth_limit: DEPTH_LIMIT,
+		}
+	}
+
+	/// Limit the nesting of containers and tags to `limit` levels, deeper
+	/// items being rejected with `Error::Limit(LimitKind::Depth)`.
+	///
+	/// Without the `alloc` feature, skipped items cannot be nested
+	/// deeper than `scan::SCANNER_DEPTH` whatever the limit.
+	pub fn set_depth_limit(&mut self, limit: usize) {
+		self.depth_limit = limit;
+	}
+
+	pub fn depth_limit(&self) -> usize {
+		self.depth_limit
+	}
+
+	pub fn reader(&self) -> &R {
+		&self.reader
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	/// Offset of the next header to be read.
+	pub fn offset(&self) -> usize {
+		if self.peeked_initial {
+			self.start
+		} else {
+			self.offset
+		}
+	}
+
+	/// The initial byte of the last peeked or read header.
+	pub fn initial(&self) -> u8 {
+		self.initial
+	}
+
+	/// Peek the initial byte of the next header.
+	#[inline]
+	pub fn peek_initial(&mut self) -> Result<u8> {
+		if !self.peeked_initial {
+			self.start = self.offset;
+			self.initial = self.reader.read_u8()?;
+			self.offset += 1;
+			self.peeked_initial = true;
+		}
+		Ok(self.initial)
+	}
+
+	/// Peek the next header without consuming it.
+	#[inline]
+	pub fn peek(&mut self) -> Result<Header> {
+		match self.peeked_header {
+			Some(header) => Ok(header),
+			None => {
+				let header = self.read_argument()?;
+				self.peeked_header = Some(header);
+				Ok(header)
+			}
+		}
+	}
+
+	/// Read and consume the next header.
+	#[inline]
+	pub fn header(&mut self) -> Result<Header> {
+		let header = self.peek()?;
+		self.peeked_initial = false;
+		self.peeked_header = None;
+		Ok(header)
+	}
+
+	/// Read the payload of a definite byte or text string.
+	#[inline]
+	pub fn read_payload<'a>(&'a mut self, len: u64) -> Result<EitherLifetime<'a, 'r>> {
+		let len = usize::try_from(len)?;
+		let payload = self.reader.read_bytes(len)?;
+		self.offset += len;
+		Ok(payload)
+	}
+
+	/// Split the payload of a definite byte string off the input,
+	/// if the reader shares its allocation.
+	#[cfg(feature = "bytes")]
+	#[inline]
+	pub(crate) fn read_shared(&mut self, len: u64) -> Result<Option<bytes::Bytes>> {
+		let shared = self.reader.read_shared(usize::try_from(len)?)?;
+		if let Some(bytes) = &shared {
+			self.offset += bytes.len();
+		}
+		Ok(shared)
+	}
+
+	/// Check that the input has been fully consumed.
+	pub fn end(&mut self) -> Result<()> {
+		match self.peek_initial() {
+			Ok(_) => Err(Error::TrailingData(self.offset())),
+			Err(Error::Eof) => Ok(()),
+			Err(error) => Err(error),
+		}
+	}
+
+	/// Read the next event. Indefinite byte and text strings are
+	/// not supported at this level, `header` must be used instead.
+	pub fn next<'a>(&'a mut self) -> Result<Event<'a>>
+	where
+		'r: 'a,
+	{
+		Ok(match self.header()? {
+			Header::Positive(value) => Event::UInt(value),
+			Header::Negative(value) => Event::NegInt(value),
+			Header::Bytes(Some(len)) => match self.read_payload(len)? {
+				EitherLifetime::Current(bytes) => Event::Bytes(bytes),
+				EitherLifetime::Other(bytes) => Event::Bytes(bytes),
+			},
+			Header::Text(Some(len)) => match self.read_payload(len)? {
+				EitherLifetime::Current(bytes) => Event::Text(core::str::from_utf8(bytes)?),
+				EitherLifetime::Other(bytes) => Event::Text(core::str::from_utf8(bytes)?),
+			},
+			Header::Bytes(None) | Header::Text(None) => {
+				return Err(Error::Unsupported(self.initial))
+			}
+			Header::Array(len) => Event::Array(len),
+			Header::Map(len) => Event::Map(len),
+			Header::Tag(tag) => Event::Tag(tag),
+			Header::Simple(value) => Event::Simple(value),
+			Header::Float(value) => Event::Float(value),
+			Header::Break => Event::Break,
+		})
+	}
+
+	/// Skip one complete data item without decoding it.
+	pub fn skip(&mut self) -> Result<()> {
+		self.skip_into(None)
+	}
+
+	/// Skip one complete data item. When `capture` is set,
+	/// the raw bytes of the item are appended to it.
+	///
+	/// The open containers are kept on an explicit stack bounded by the
+	/// depth limit, so that no input can exhaust the call stack.
+	pub(crate) fn skip_into(&mut self, mut capture: Option<&mut Capture>) -> Result<()> {
+		let mut frames = Frames::new(self.depth_limit.saturating_sub(self.depth));
+		loop {
+			self.header()?;
+			if let Some(buffer) = capture.as_deref_mut() {
+				self.capture_header(buffer);
+			}
+			let len = frames.accept(self.initial, self.argument)?;
+			if len > 0 {
+				self.skip_payload(len, capture.as_deref_mut())?;
+			}
+			if frames.is_empty() {
+				return Ok(());
+			}
+		}
+	}
+
+	#[inline]
+	fn skip_payload(&mut self, len: u64, capture: Option<&mut Capture>) -> Result<()> {
+		let len = usize::try_from(len)?;
+		match capture {
+			None => self.reader.skip_bytes(len)?,
+			Some(buffer) => match self.reader.read_bytes(len)? {
+				EitherLifetime::Current(bytes) => buffer.extend_from_slice(bytes),
+				EitherLifetime::Other(bytes) => buffer.extend_from_slice(bytes),
+			},
+		}
+		self.offset += len;
+		Ok(())
+	}
+
+	/// Enter a container or a tag, failing past the depth limit.
+	#[inline]
+	pub(crate) fn enter(&mut self) -> Result<()> {
+		if self.depth >= self.depth_limit {
+			return Err(Error::Limit(LimitKind::Depth));
+		}
+		self.depth += 1;
+		Ok(())
+	}
+
+	/// Leave the container or the tag last entered.
+	#[inline]
+	pub(crate) fn leave(&mut self) {
+		self.depth = self.depth.saturating_sub(1);
+	}
+
+	/// Consume the next header if it is a break.
+	#[inline]
+	pub(crate) fn skip_break(&mut self, capture: Option<&mut Capture>) -> Result<bool> {
+		if self.peek()? != Header::Break {
+			return Ok(false);
+		}
+		self.header()?;
+		if let Some(buffer) = capture {
+			buffer.push(HEADER_BREAK);
+		}
+		Ok(true)
+	}
+
+	#[inline]
+	fn read_argument(&mut self) -> Result<Header> {
+		let initial = self.peek_initial()?;
+		let info = initial & 0x1F;
+		let argument = match info {
+			n if n < SIZE_8 => Some(n as u64),
+			SIZE_8 => Some(self.reader.read_u8()? as u64),
+			SIZE_16 => Some(self.reader.read_u16()? as u64),
+			SIZE_32 => Some(self.reader.read_u32()? as u64),
+			SIZE_64 => Some(self.reader.read_u64()?),
+			SIZE_INFINITE => None,
+			_ => return Err(Error::Unassigned(initial)),
+		};
+		self.offset += argument_size(initial)?;
+		self.argument = argument.unwrap_or(0);
+		Ok(match (initial >> 5, argument) {
+			(MAJOR_POSITIVE, Some(value)) => Header::Positive(value),
+			(MAJOR_NEGATIVE, Some(value)) => Header::Negative(value),
+			(MAJOR_BYTE, len) => Header::Bytes(len),
+			(MAJOR_TEXT, len) => Header::Text(len),
+			(MAJOR_ARRAY, len) => Header::Array(len),
+			(MAJOR_MAP, len) => Header::Map(len),
+			(MAJOR_TAG, Some(tag)) => Header::Tag(tag),
+			(MAJOR_PRIMITIVE, Some(value)) => match info {
+				SIZE_8 if value < 32 => return Err(Error::Unassigned(initial)),
+				SIZE_16 => Header::Float(half::f16::from_bits(value as u16).into()),
+				SIZE_32 => Header::Float(f32::from_bits(value as u32).into()),
+				SIZE_64 => Header::Float(f64::from_bits(value)),
+				_ => Header::Simple(value as u8),
+			},
+			(MAJOR_PRIMITIVE, None) => Header::Break,
+			_ => return Err(Error::Unassigned(initial)),
+		})
+	}
+
+	#[inline]
+	fn capture_header(&self, buffer: &mut Capture) {
+		let size = match self.initial & 0x1F {
+			SIZE_8 => 1,
+			SIZE_16 => 2,
+			SIZE_32 => 4,
+			SIZE_64 => 8,
+			_ => 0,
+		};
+		buffer.push(self.initial);
+		buffer.extend_from_slice(&self.argument.to_be_bytes()[8 - size..]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::raw::RawCbor;
+	use crate::view::CborView;
+	use serde_derive::Deserialize;
+
+	const DEEP: usize = 1 << 20;
+
+	/// Arrays of one element, nested a million times.
+	static ARRAYS: [u8; DEEP] = [0x81; DEEP];
+
+	/// Tags, nested a million times.
+	static TAGS: [u8; DEEP] = [0xC1; DEEP];
+
+	fn decoder(bytes: &[u8]) -> Decoder<SliceReader<'_>> {
+		Decoder::new(SliceReader::new(bytes))
+	}
+
+	#[test]
+	fn headers_of_every_major_type() {
+		let cases: &[(&[u8], Header)] = &[
+			(&[0x17], Header::Positive(23)),
+			(&[0x18, 0x18], Header::Positive(24)),
+			(&[0x19, 0x01, 0x00], Header::Positive(256)),
+			(&[0x1A, 0x00, 0x01, 0x00, 0x00], Header::Positive(65536)),
+			(&[0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], Header::Positive(u64::MAX)),
+			(&[0x20], Header::Negative(0)),
+			(&[0x45], Header::Bytes(Some(5))),
+			(&[0x5F], Header::Bytes(None)),
+			(&[0x78, 0x20], Header::Text(Some(32))),
+			(&[0x7F], Header::Text(None)),
+			(&[0x80], Header::Array(Some(0))),
+			(&[0x9F], Header::Array(None)),
+			(&[0xA1], Header::Map(Some(1))),
+			(&[0xBF], Header::Map(None)),
+			(&[0xD8, 0x20], Header::Tag(32)),
+			(&[0xF4], Header::Simple(SIMPLE_FALSE)),
+			(&[0xF8, 0xFF], Header::Simple(255)),
+			(&[0xF9, 0x3C, 0x00], Header::Float(1.0)),
+			(&[0xFA, 0x3F, 0xC0, 0x00, 0x00], Header::Float(1.5)),
+			(&[0xFB, 0x40, 0x04, 0, 0, 0, 0, 0, 0], Header::Float(2.5)),
+			(&[0xFF], Header::Break),
+		];
+		for (bytes, header) in cases {
+			let mut decoder = decoder(bytes);
+			assert_eq!(decoder.header().unwrap(), *header, "{:02x?}", bytes);
+			assert_eq!(decoder.initial(), bytes[0]);
+			assert_eq!(decoder.offset(), bytes.len());
+		}
+	}
+
+	#[test]
+	fn malformed_headers() {
+		for initial in [0x1C, 0x3F, 0xDF, 0xFC] {
+			assert!(matches!(decoder(&[initial]).header(), Err(Error::Unassigned(i)) if i == initial));
+		}
+		assert!(matches!(decoder(&[0xF8, 0x1F]).header(), Err(Error::Unassigned(0xF8))));
+		assert!(matches!(decoder(&[0x19, 0x01]).header(), Err(Error::Eof)));
+		assert!(matches!(decoder(&[]).header(), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn peek_does_not_consume() {
+		let mut decoder = decoder(&[0x01, 0x19, 0x01, 0x00]);
+		decoder.header().unwrap();
+		assert_eq!(decoder.peek_initial().unwrap(), 0x19);
+		assert_eq!(decoder.offset(), 1);
+		assert_eq!(decoder.peek().unwrap(), Header::Positive(256));
+		assert_eq!(decoder.offset(), 1);
+		assert_eq!(decoder.header().unwrap(), Header::Positive(256));
+		assert_eq!(decoder.offset(), 4);
+		assert!(decoder.end().is_ok());
+	}
+
+	#[test]
+	fn end_reports_the_trailing_offset() {
+		let mut decoder = decoder(&[0x01, 0x02]);
+		decoder.header().unwrap();
+		assert!(matches!(decoder.end(), Err(Error::TrailingData(1))));
+		// The trailing header is only peeked.
+		assert_eq!(decoder.header().unwrap(), Header::Positive(2));
+	}
+
+	#[test]
+	fn events() {
+		// [_ -2, h'01', "é", {1: 1(true)}, undefined]
+		let bytes = [
+			0x9F, 0x21, 0x41, 0x01, 0x62, 0xC3, 0xA9, 0xA1, 0x01, 0xC1, 0xF5, 0xF7, 0xFF,
+		];
+		let mut decoder = decoder(&bytes);
+		assert_eq!(decoder.next().unwrap(), Event::Array(None));
+		assert_eq!(decoder.next().unwrap(), Event::NegInt(1));
+		assert_eq!(decoder.next().unwrap(), Event::Bytes(&[0x01]));
+		assert_eq!(decoder.next().unwrap(), Event::Text("é"));
+		assert_eq!(decoder.next().unwrap(), Event::Map(Some(1)));
+		assert_eq!(decoder.next().unwrap(), Event::UInt(1));
+		assert_eq!(decoder.next().unwrap(), Event::Tag(1));
+		assert_eq!(decoder.next().unwrap(), Event::Simple(SIMPLE_TRUE));
+		assert_eq!(decoder.next().unwrap(), Event::Simple(SIMPLE_UNDEFINED));
+		assert_eq!(decoder.next().unwrap(), Event::Break);
+		assert!(matches!(decoder.next(), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn events_reject_what_they_cannot_borrow() {
+		assert!(matches!(decoder(&[0x5F, 0xFF]).next(), Err(Error::Unsupported(0x5F))));
+		assert!(matches!(decoder(&[0x61, 0xFF]).next(), Err(Error::Other(OtherKind::Utf8, _))));
+		assert!(matches!(decoder(&[0x42, 0x00]).next(), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn payload_borrows_from_the_input() {
+		let bytes = [0x43, 0x01, 0x02, 0x03];
+		let mut decoder = decoder(&bytes);
+		assert_eq!(decoder.header().unwrap(), Header::Bytes(Some(3)));
+		match decoder.read_payload(3).unwrap() {
+			EitherLifetime::Other(payload) => assert_eq!(payload.as_ptr(), bytes[1..].as_ptr()),
+			EitherLifetime::Current(_) => panic!("payload copied"),
+		}
+	}
+
+	#[test]
+	fn offsets_of_any_reader() {
+		/// A reader implementing only what is required.
+		struct Minimal<'a>(&'a [u8]);
+
+		impl<'a> Reader<'a> for Minimal<'a> {
+			fn read_bytes<'b>(&'b mut self, size: usize) -> Result<EitherLifetime<'b, 'a>> {
+				if size > self.0.len() {
+					return Err(Error::Eof);
+				}
+				let (bytes, rest) = self.0.split_at(size);
+				self.0 = rest;
+				Ok(EitherLifetime::Other(bytes))
+			}
+		}
+
+		// [1, 32], h'aa'
+		let mut decoder = Decoder::new(Minimal(&[0x82, 0x01, 0x18, 0x20, 0x41, 0xAA]));
+		decoder.skip().unwrap();
+		assert_eq!(decoder.offset(), 4);
+		assert_eq!(decoder.peek().unwrap(), Header::Bytes(Some(1)));
+		assert_eq!(decoder.offset(), 4);
+		assert!(matches!(decoder.next().unwrap(), Event::Bytes(&[0xAA])));
+		assert_eq!(decoder.offset(), 6);
+		decoder.end().unwrap();
+	}
+
+	fn skip(bytes: &[u8], limit: usize) -> Result<usize> {
+		let mut decoder = Decoder::new(SliceReader::new(bytes));
+		decoder.set_depth_limit(limit);
+		decoder.skip()?;
+		Ok(decoder.offset())
+	}
+
+	#[test]
+	fn skip_walks_every_kind_of_item() {
+		// [_ 1, {"a": (_ h'01', h'')}, 1(2), []]
+		let item = [
+			0x9F, 0x01, 0xA1, 0x61, 0x61, 0x5F, 0x41, 0x01, 0x40, 0xFF, 0xC1, 0x02, 0x80, 0xFF, 0x00,
+		];
+		assert_eq!(skip(&item, DEPTH_LIMIT).unwrap(), 14);
+		assert!(matches!(skip(&item[..13], DEPTH_LIMIT), Err(Error::Eof)));
+		assert!(matches!(skip(&[0x5F, 0x61, 0x61, 0xFF], DEPTH_LIMIT), Err(Error::Unexpected(0x61, _))));
+		assert!(matches!(skip(&[0xFF], DEPTH_LIMIT), Err(Error::Unexpected(HEADER_BREAK, _))));
+	}
+
+	#[test]
+	fn skip_pairs_keys_and_values() {
+		// {_ 1: 2, 3: {_ }}
+		assert_eq!(skip(&[0xBF, 0x01, 0x02, 0x03, 0xBF, 0xFF, 0xFF], DEPTH_LIMIT).unwrap(), 7);
+		assert_eq!(skip(&[0xBF, 0xFF], DEPTH_LIMIT).unwrap(), 2);
+		// A key without its value.
+		assert!(matches!(skip(&[0xBF, 0x01, 0xFF], DEPTH_LIMIT), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(skip(&[0xBF, 0x01, 0x02, 0x03, 0xFF], DEPTH_LIMIT), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(RawCbor::from_bytes(&[0xBF, 0x01, 0xFF]), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(CborView::new(&[0xBF, 0x01, 0xFF]), Err(Error::Unexpected(HEADER_BREAK, _))));
+	}
+
+	#[test]
+	fn skip_depth_is_limited() {
+		assert!(matches!(skip(&ARRAYS, DEPTH_LIMIT), Err(Error::Limit(LimitKind::Depth))));
+		assert!(matches!(skip(&TAGS, DEPTH_LIMIT), Err(Error::Limit(LimitKind::Depth))));
+		assert!(matches!(RawCbor::from_bytes(&TAGS), Err(Error::Limit(LimitKind::Depth))));
+		assert!(matches!(CborView::new(&ARRAYS), Err(Error::Limit(LimitKind::Depth))));
+	}
+
+	#[test]
+	fn skip_depth_limit_is_configurable() {
+		// [[[0]]]
+		assert_eq!(skip(&[0x81, 0x81, 0x81, 0x00], 3).unwrap(), 4);
+		assert!(matches!(skip(&[0x81, 0x81, 0x81, 0x00], 2), Err(Error::Limit(LimitKind::Depth))));
+		// Empty containers do not count as a level.
+		assert_eq!(skip(&[0x81, 0x80], 1).unwrap(), 2);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn skip_deeper_than_the_default() {
+		let mut bytes = [0x81; 1001];
+		bytes[1000] = 0x00;
+		assert!(matches!(skip(&bytes, DEPTH_LIMIT), Err(Error::Limit(LimitKind::Depth))));
+		assert_eq!(skip(&bytes, 1000).unwrap(), 1001);
+	}
+
+	#[test]
+	fn ignored_fields_depth_is_limited() {
+		#[derive(Deserialize, Debug)]
+		struct Known {
+			a: u32,
+		}
+
+		let mut bytes = [0x81; 200_007];
+		// {"a": 1, "z": [[[...]]]}
+		bytes[..6].copy_from_slice(&[0xA2, 0x61, b'a', 0x01, 0x61, b'z']);
+		let result = crate::from_slice::<Known>(&bytes);
+		assert!(matches!(result, Err(Error::Limit(LimitKind::Depth))));
+		bytes[6..9].copy_from_slice(&[0x81, 0x81, 0x00]);
+		assert_eq!(crate::from_slice_prefix::<Known>(&bytes).unwrap().0.a, 1);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn deserialized_depth_is_limited() {
+		#[derive(Deserialize)]
+		#[allow(dead_code)]
+		struct Node {
+			next: Option<alloc::boxed::Box<Node>>,
+		}
+
+		// {"next": {"next": ...}} nested far deeper than the limit.
+		let mut bytes = alloc::vec::Vec::new();
+		for _ in 0..100_000 {
+			bytes.extend_from_slice(&[0xA1, 0x64, b'n', b'e', b'x', b't']);
+		}
+		bytes.push(0xF6);
+		let result = crate::from_slice::<Node>(&bytes);
+		assert!(matches!(result, Err(Error::Limit(LimitKind::Depth))));
+	}
+}
@@ -1,61 +1,166 @@
 use crate::cbor::*;
+use crate::decode::*;
 use crate::error::*;
 use crate::read::*;
 use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use serde::de;
-use std::convert::TryFrom;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
 
 pub struct Deserializer<R> {
-	reader: R,
-	peek: Option<u8>,
+	decoder: Decoder<R>,
 }
 
 impl<'de, R: Reader<'de>> Deserializer<R> {
 	pub fn new(reader: R) -> Self {
 		Deserializer {
-			reader: reader,
-			peek: None,
+			decoder: Decoder::new(reader),
 		}
 	}
 
+	/// Offset of the next byte to be deserialized.
+	pub fn offset(&self) -> usize {
+		self.decoder.offset()
+	}
+
+	/// Check that the input has been fully consumed.
+	pub fn end(&mut self) -> Result<()> {
+		self.decoder.end()
+	}
+
+	/// Limit the nesting of containers to `limit` levels,
+	/// `decode::DEPTH_LIMIT` by default.
+	pub fn set_depth_limit(&mut self, limit: usize) {
+		self.decoder.set_depth_limit(limit);
+	}
+
+	/// Turn this deserializer into an iterator over a sequence of
+	/// concatenated data items (RFC 8742).
+	pub fn into_stream<T>(self) -> StreamDeserializer<'de, R, T>
+	where
+		T: de::Deserialize<'de>,
+	{
+		StreamDeserializer::new(self)
+	}
+
 	#[inline]
-	fn peek(&mut self) -> Result<u8> {
-		match self.peek {
-			Some(val) => Ok(val),
-			None => {
-				self.peek = Some(self.reader.read_u8()?);
-				Ok(self.peek.unwrap())
-			}
+	fn unexpected(&self, expected: &'static str) -> Error {
+		Error::Unexpected(self.decoder.initial(), expected)
+	}
+
+	#[inline]
+	fn unsupported(&self) -> Error {
+		Error::Unsupported(self.decoder.initial())
+	}
+
+	#[inline]
+	fn visit_bytes<V>(&mut self, len: u64, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		match self.decoder.read_payload(len)? {
+			EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
+			EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
 		}
 	}
 
 	#[inline]
-	fn consume(&mut self) {
-		self.peek = None;
+	fn visit_text<V>(&mut self, len: u64, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		match self.decoder.read_payload(len)? {
+			EitherLifetime::Current(bytes) => visitor.visit_str(core::str::from_utf8(bytes)?),
+			EitherLifetime::Other(bytes) => visitor.visit_borrowed_str(core::str::from_utf8(bytes)?),
+		}
 	}
 
 	#[inline]
-	fn peek_and_consume(&mut self) -> Result<u8> {
-		let peek = self.peek();
-		self.consume();
-		peek
+	fn visit_seq<V>(&mut self, len: u64, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		let len = usize::try_from(len)?;
+		self.decoder.enter()?;
+		let value = visitor.visit_seq(SeqAccess { de: self, len });
+		self.decoder.leave();
+		value
 	}
 
 	#[inline]
-	fn deserialize_f16<V>(&mut self, visitor: V) -> Result<V::Value>
+	fn visit_map<V>(&mut self, len: u64, visitor: V) -> Result<V::Value>
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_FLOAT_16 {
-			visitor.visit_f32(half::f16::from_bits(self.reader.read_u16()?).into())
+		let len = usize::try_from(len)?;
+		self.decoder.enter()?;
+		let value = visitor.visit_map(MapAccess { de: self, len });
+		self.decoder.leave();
+		value
+	}
+
+	#[inline]
+	fn visit_float<V>(&mut self, value: f64, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		if self.decoder.initial() == HEADER_FLOAT_64 {
+			visitor.visit_f64(value)
 		} else {
-			Err(Error::Unexpected(peek, "floating point"))
+			visitor.visit_f32(value as f32)
+		}
+	}
+
+	/// Hand the exact bytes of the next data item to the visitor,
+	/// borrowed from the input when the reader allows it.
+	fn deserialize_raw<V>(&mut self, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		// When the reader can borrow the input, the item is skipped and then
+		// borrowed, otherwise its bytes are captured while skipping it.
+		let start = self.offset();
+		if self.decoder.reader().borrow_last(0).is_some() {
+			self.decoder.skip()?;
+		}
+		match self.decoder.reader().borrow_last(self.offset() - start) {
+			Some(bytes) => visitor.visit_borrowed_bytes(bytes),
+			#[cfg(feature = "alloc")]
+			None => {
+				let mut buffer = Vec::new();
+				self.decoder.skip_into(Some(&mut buffer))?;
+				visitor.visit_byte_buf(buffer)
+			}
+			#[cfg(not(feature = "alloc"))]
+			None => Err(Error::Unsupported(self.decoder.peek_initial()?)),
 		}
 	}
 }
 
-impl<'de, 'r, R> serde::Deserializer<'de> for &'r mut Deserializer<R>
+#[cfg(feature = "bytes")]
+impl<'de, R: Reader<'de>> Deserializer<R> {
+	/// Deserialize a definite byte string, split off the input
+	/// when the reader can share it.
+	fn deserialize_shared<V>(&mut self, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		match self.decoder.peek()? {
+			Header::Bytes(Some(len)) => {
+				self.decoder.header()?;
+				match self.decoder.read_shared(len)? {
+					Some(bytes) => crate::buf::visit_shared(bytes, visitor),
+					None => self.visit_bytes(len, visitor),
+				}
+			}
+			_ => de::Deserializer::deserialize_bytes(self, visitor),
+		}
+	}
+}
+
+impl<'de, R> serde::Deserializer<'de> for &mut Deserializer<R>
 where
 	R: Reader<'de>,
 {
@@ -66,200 +171,23 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		match self.peek_and_consume()? {
-			HEADER_FALSE => visitor.visit_bool(false),
-			HEADER_TRUE => visitor.visit_bool(true),
-			HEADER_NULL => visitor.visit_none(),
-			HEADER_UNDEFINED => visitor.visit_unit(),
-			HEADER_FLOAT_16 => {
-				visitor.visit_f32(half::f16::from_bits(self.reader.read_u16()?).into())
-			}
-			HEADER_FLOAT_32 => visitor.visit_f32(self.reader.read_f32()?),
-			HEADER_FLOAT_64 => visitor.visit_f64(self.reader.read_f64()?),
-			peek if HEADER_POSITIVE_START <= peek && peek < HEADER_POSITIVE_8 => {
-				visitor.visit_u8(peek & 0x1F)
-			}
-			HEADER_POSITIVE_8 => visitor.visit_u8(self.reader.read_u8()?),
-			HEADER_POSITIVE_16 => visitor.visit_u16(self.reader.read_u16()?),
-			HEADER_POSITIVE_32 => visitor.visit_u32(self.reader.read_u32()?),
-			HEADER_POSITIVE_64 => visitor.visit_u64(self.reader.read_u64()?),
-			peek if HEADER_NEGATIVE_START <= peek && peek < HEADER_NEGATIVE_8 => {
-				visitor.visit_i8(-1 - i8::try_from(peek & 0x1F)?)
-			}
-			HEADER_NEGATIVE_8 => visitor.visit_i8(-1 - i8::try_from(self.reader.read_u8()?)?),
-			HEADER_NEGATIVE_16 => visitor.visit_i16(-1 - i16::try_from(self.reader.read_u16()?)?),
-			HEADER_NEGATIVE_32 => visitor.visit_i32(-1 - i32::try_from(self.reader.read_u32()?)?),
-			HEADER_NEGATIVE_64 => visitor.visit_i64(-1 - i64::try_from(self.reader.read_u64()?)?),
-			peek if HEADER_BYTE_START <= peek && peek < HEADER_BYTE_8 => {
-				match self.reader.read_bytes((peek & 0x1F) as usize)? {
-					EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-					EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
-				}
-			}
-			HEADER_BYTE_8 => {
-				let len = (self.reader.read_u8()?) as usize;
-				match self.reader.read_bytes(len)? {
-					EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-					EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
-				}
-			}
-			HEADER_BYTE_16 => {
-				let len = (self.reader.read_u16()?) as usize;
-				match self.reader.read_bytes(len)? {
-					EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-					EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
-				}
-			}
-			HEADER_BYTE_32 => {
-				let len = usize::try_from(self.reader.read_u32()?)?;
-				match self.reader.read_bytes(len)? {
-					EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-					EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
-				}
-			}
-			HEADER_BYTE_64 => {
-				let len = usize::try_from(self.reader.read_u64()?)?;
-				match self.reader.read_bytes(len)? {
-					EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-					EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
-				}
-			}
-			peek if HEADER_TEXT_START <= peek && peek < HEADER_TEXT_8 => {
-				match self.reader.read_bytes((peek & 0x1F) as usize)? {
-					EitherLifetime::Current(bytes) => {
-						visitor.visit_str(std::str::from_utf8(bytes)?)
-					}
-					EitherLifetime::Other(bytes) => {
-						visitor.visit_borrowed_str(std::str::from_utf8(bytes)?)
-					}
-				}
-			}
-			HEADER_TEXT_8 => {
-				let size = (self.reader.read_u8()?) as usize;
-				match self.reader.read_bytes(size)? {
-					EitherLifetime::Current(bytes) => {
-						visitor.visit_str(std::str::from_utf8(bytes)?)
-					}
-					EitherLifetime::Other(bytes) => {
-						visitor.visit_borrowed_str(std::str::from_utf8(bytes)?)
-					}
-				}
-			}
-			HEADER_TEXT_16 => {
-				self.consume();
-				let size = (self.reader.read_u16()?) as usize;
-				match self.reader.read_bytes(size)? {
-					EitherLifetime::Current(bytes) => {
-						visitor.visit_str(std::str::from_utf8(bytes)?)
-					}
-					EitherLifetime::Other(bytes) => {
-						visitor.visit_borrowed_str(std::str::from_utf8(bytes)?)
-					}
-				}
-			}
-			HEADER_TEXT_32 => {
-				let size = usize::try_from(self.reader.read_u32()?)?;
-				match self.reader.read_bytes(size)? {
-					EitherLifetime::Current(bytes) => {
-						visitor.visit_str(std::str::from_utf8(bytes)?)
-					}
-					EitherLifetime::Other(bytes) => {
-						visitor.visit_borrowed_str(std::str::from_utf8(bytes)?)
-					}
-				}
-			}
-			HEADER_TEXT_64 => {
-				let size = usize::try_from(self.reader.read_u64()?)?;
-				match self.reader.read_bytes(size)? {
-					EitherLifetime::Current(bytes) => {
-						visitor.visit_str(std::str::from_utf8(bytes)?)
-					}
-					EitherLifetime::Other(bytes) => {
-						visitor.visit_borrowed_str(std::str::from_utf8(bytes)?)
-					}
-				}
-			}
-			peek if HEADER_ARRAY_START <= peek && peek < HEADER_ARRAY_8 => {
-				visitor.visit_seq(SeqAccess {
-					de: self,
-					len: (peek & 0x1F) as usize,
-				})
-			}
-			HEADER_ARRAY_8 => {
-				let size = (self.reader.read_u8()?) as usize;
-				visitor.visit_seq(SeqAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_ARRAY_16 => {
-				let size = (self.reader.read_u16()?) as usize;
-				visitor.visit_seq(SeqAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_ARRAY_32 => {
-				let size = usize::try_from(self.reader.read_u32()?)?;
-				visitor.visit_seq(SeqAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_ARRAY_64 => {
-				let size = usize::try_from(self.reader.read_u64()?)?;
-				visitor.visit_seq(SeqAccess {
-					de: self,
-					len: size,
-				})
-			}
-			peek if HEADER_MAP_START <= peek && peek < HEADER_MAP_8 => {
-				visitor.visit_map(MapAccess {
-					de: self,
-					len: (peek & 0x1F) as usize,
-				})
-			}
-			HEADER_MAP_8 => {
-				let size = (self.reader.read_u8()?) as usize;
-				visitor.visit_map(MapAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_MAP_16 => {
-				let size = (self.reader.read_u16()?) as usize;
-				visitor.visit_map(MapAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_MAP_32 => {
-				let size = usize::try_from(self.reader.read_u32()?)?;
-				visitor.visit_map(MapAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_MAP_64 => {
-				let size = usize::try_from(self.reader.read_u64()?)?;
-				visitor.visit_map(MapAccess {
-					de: self,
-					len: size,
-				})
-			}
-			HEADER_BYTE_INFINITE => Err(Error::Unsupported(HEADER_BYTE_INFINITE)),
-			HEADER_TEXT_INFINITE => Err(Error::Unsupported(HEADER_TEXT_INFINITE)),
-			HEADER_ARRAY_INFINITE => Err(Error::Unsupported(HEADER_ARRAY_INFINITE)),
-			HEADER_MAP_INFINITE => Err(Error::Unsupported(HEADER_MAP_INFINITE)),
-			peek if HEADER_TAG_START <= peek && peek < HEADER_TAG_8 => {
-				Err(Error::Unsupported(peek))
-			}
-			HEADER_TAG_8 => Err(Error::Unsupported(HEADER_TAG_8)),
-			HEADER_TAG_16 => Err(Error::Unsupported(HEADER_TAG_16)),
-			HEADER_TAG_32 => Err(Error::Unsupported(HEADER_TAG_32)),
-			HEADER_TAG_64 => Err(Error::Unsupported(HEADER_TAG_64)),
-			HEADER_BREAK => Err(Error::Unexpected(HEADER_BREAK, "any other header")),
-			peek => Err(Error::Unassigned(peek)),
+		match self.decoder.header()? {
+			Header::Positive(value) => visitor.visit_u64(value),
+			Header::Negative(value) => visitor.visit_i64(-1 - i64::try_from(value)?),
+			Header::Bytes(Some(len)) => self.visit_bytes(len, visitor),
+			Header::Text(Some(len)) => self.visit_text(len, visitor),
+			Header::Array(Some(len)) => self.visit_seq(len, visitor),
+			Header::Map(Some(len)) => self.visit_map(len, visitor),
+			Header::Simple(SIMPLE_FALSE) => visitor.visit_bool(false),
+			Header::Simple(SIMPLE_TRUE) => visitor.visit_bool(true),
+			Header::Simple(SIMPLE_NULL) => visitor.visit_none(),
+			Header::Simple(SIMPLE_UNDEFINED) => visitor.visit_unit(),
+			Header::Simple(_) => Err(Error::Unassigned(self.decoder.initial())),
+			Header::Float(value) => self.visit_float(value, visitor),
+			Header::Bytes(None) | Header::Text(None) => Err(self.unsupported()),
+			Header::Array(None) | Header::Map(None) => Err(self.unsupported()),
+			Header::Tag(_) => Err(self.unsupported()),
+			Header::Break => Err(self.unexpected("any other header")),
 		}
 	}
 
@@ -268,14 +196,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_POSITIVE_8 {
-			visitor.visit_u8(self.reader.read_u8()?)
-		} else if HEADER_POSITIVE_START <= peek && peek < HEADER_POSITIVE_8 {
-			visitor.visit_u8(peek & 0x1F)
-		} else {
-			Err(Error::Unexpected(peek, "unsigned integer"))
-		}
+		self.deserialize_u64(visitor)
 	}
 
 	#[inline]
@@ -283,12 +204,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_POSITIVE_16 {
-			self.consume();
-			visitor.visit_u16(self.reader.read_u16()?)
-		} else {
-			self.deserialize_u8(visitor)
-		}
+		self.deserialize_u64(visitor)
 	}
 
 	#[inline]
@@ -296,12 +212,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_POSITIVE_32 {
-			self.consume();
-			visitor.visit_u32(self.reader.read_u32()?)
-		} else {
-			self.deserialize_u16(visitor)
-		}
+		self.deserialize_u64(visitor)
 	}
 
 	#[inline]
@@ -309,11 +220,9 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_POSITIVE_64 {
-			self.consume();
-			visitor.visit_u64(self.reader.read_u64()?)
-		} else {
-			self.deserialize_u32(visitor)
+		match self.decoder.header()? {
+			Header::Positive(value) => visitor.visit_u64(value),
+			_ => Err(self.unexpected("unsigned integer")),
 		}
 	}
 
@@ -322,18 +231,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_POSITIVE_8 {
-			visitor.visit_u8(self.reader.read_u8()?)
-		} else if peek == HEADER_NEGATIVE_8 {
-			visitor.visit_i8(-1 - i8::try_from(self.reader.read_u8()?)?)
-		} else if HEADER_POSITIVE_START <= peek && peek < HEADER_POSITIVE_8 {
-			visitor.visit_u8(peek & 0x1F)
-		} else if HEADER_NEGATIVE_START <= peek && peek < HEADER_NEGATIVE_8 {
-			visitor.visit_i8(-1 - i8::try_from(peek & 0x1F)?)
-		} else {
-			Err(Error::Unexpected(peek, "signed integer"))
-		}
+		self.deserialize_i64(visitor)
 	}
 
 	#[inline]
@@ -341,16 +239,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek()?;
-		if peek == HEADER_POSITIVE_16 {
-			self.consume();
-			visitor.visit_u16(self.reader.read_u16()?)
-		} else if peek == HEADER_NEGATIVE_16 {
-			self.consume();
-			visitor.visit_i16(-1 - (i16::try_from(self.reader.read_u16()?)?))
-		} else {
-			self.deserialize_i8(visitor)
-		}
+		self.deserialize_i64(visitor)
 	}
 
 	#[inline]
@@ -358,16 +247,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek()?;
-		if peek == HEADER_POSITIVE_32 {
-			self.consume();
-			visitor.visit_u32(self.reader.read_u32()?)
-		} else if peek == HEADER_NEGATIVE_32 {
-			self.consume();
-			visitor.visit_i32(-1 - (i32::try_from(self.reader.read_u32()?)?))
-		} else {
-			self.deserialize_i16(visitor)
-		}
+		self.deserialize_i64(visitor)
 	}
 
 	#[inline]
@@ -375,15 +255,10 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek()?;
-		if peek == HEADER_POSITIVE_64 {
-			self.consume();
-			visitor.visit_u64(self.reader.read_u64()?)
-		} else if peek == HEADER_NEGATIVE_64 {
-			self.consume();
-			visitor.visit_i64(-1 - (i64::try_from(self.reader.read_u64()?)?))
-		} else {
-			self.deserialize_i32(visitor)
+		match self.decoder.header()? {
+			Header::Positive(value) => visitor.visit_u64(value),
+			Header::Negative(value) => visitor.visit_i64(-1 - i64::try_from(value)?),
+			_ => Err(self.unexpected("signed integer")),
 		}
 	}
 
@@ -392,13 +267,10 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_TRUE {
-			visitor.visit_bool(true)
-		} else if peek == HEADER_FALSE {
-			visitor.visit_bool(false)
-		} else {
-			Err(Error::Unexpected(peek, "boolean"))
+		match self.decoder.header()? {
+			Header::Simple(SIMPLE_TRUE) => visitor.visit_bool(true),
+			Header::Simple(SIMPLE_FALSE) => visitor.visit_bool(false),
+			_ => Err(self.unexpected("boolean")),
 		}
 	}
 
@@ -407,8 +279,8 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_NULL {
-			self.consume();
+		if self.decoder.peek()? == Header::Simple(SIMPLE_NULL) {
+			self.decoder.header()?;
 			visitor.visit_none()
 		} else {
 			visitor.visit_some(self)
@@ -420,18 +292,10 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let size: usize = match self.peek_and_consume()? {
-			n if HEADER_TEXT_START <= n && n < HEADER_TEXT_8 => (n & 0x1F) as usize,
-			HEADER_TEXT_8 => (self.reader.read_u8()?) as usize,
-			HEADER_TEXT_16 => (self.reader.read_u16()?) as usize,
-			HEADER_TEXT_32 => usize::try_from(self.reader.read_u32()?)?,
-			HEADER_TEXT_64 => usize::try_from(self.reader.read_u64()?)?,
-			HEADER_TEXT_INFINITE => return Err(Error::Unsupported(HEADER_TEXT_INFINITE)),
-			n => return Err(Error::Unexpected(n, "string")),
-		};
-		match self.reader.read_bytes(size)? {
-			EitherLifetime::Current(bytes) => visitor.visit_str(std::str::from_utf8(bytes)?),
-			EitherLifetime::Other(bytes) => visitor.visit_borrowed_str(std::str::from_utf8(bytes)?),
+		match self.decoder.header()? {
+			Header::Text(Some(len)) => self.visit_text(len, visitor),
+			Header::Text(None) => Err(self.unsupported()),
+			_ => Err(self.unexpected("string")),
 		}
 	}
 
@@ -456,18 +320,10 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let size: usize = match self.peek_and_consume()? {
-			n if HEADER_BYTE_START <= n && n < HEADER_BYTE_8 => (n & 0x1F) as usize,
-			HEADER_BYTE_8 => (self.reader.read_u8()?) as usize,
-			HEADER_BYTE_16 => (self.reader.read_u16()?) as usize,
-			HEADER_BYTE_32 => usize::try_from(self.reader.read_u32()?)?,
-			HEADER_BYTE_64 => usize::try_from(self.reader.read_u64()?)?,
-			HEADER_BYTE_INFINITE => return Err(Error::Unsupported(HEADER_BYTE_INFINITE)),
-			n => return Err(Error::Unexpected(n, "byte")),
-		};
-		match self.reader.read_bytes(size)? {
-			EitherLifetime::Current(bytes) => visitor.visit_bytes(bytes),
-			EitherLifetime::Other(bytes) => visitor.visit_borrowed_bytes(bytes),
+		match self.decoder.header()? {
+			Header::Bytes(Some(len)) => self.visit_bytes(len, visitor),
+			Header::Bytes(None) => Err(self.unsupported()),
+			_ => Err(self.unexpected("byte")),
 		}
 	}
 
@@ -484,12 +340,7 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_FLOAT_32 {
-			self.consume();
-			visitor.visit_f32(self.reader.read_f32()?)
-		} else {
-			self.deserialize_f16(visitor)
-		}
+		self.deserialize_f64(visitor)
 	}
 
 	#[inline]
@@ -497,11 +348,9 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		if self.peek()? == HEADER_FLOAT_64 {
-			self.consume();
-			visitor.visit_f64(self.reader.read_f64()?)
-		} else {
-			self.deserialize_f32(visitor)
+		match self.decoder.header()? {
+			Header::Float(value) => self.visit_float(value, visitor),
+			_ => Err(self.unexpected("floating point")),
 		}
 	}
 
@@ -510,11 +359,9 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_UNDEFINED {
-			visitor.visit_unit()
-		} else {
-			Err(Error::Unexpected(peek, "unit"))
+		match self.decoder.header()? {
+			Header::Simple(SIMPLE_UNDEFINED) => visitor.visit_unit(),
+			_ => Err(self.unexpected("unit")),
 		}
 	}
 
@@ -523,19 +370,24 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let peek = self.peek_and_consume()?;
-		if peek == HEADER_UNDEFINED {
-			visitor.visit_unit()
-		} else {
-			Err(Error::Unexpected(peek, "unit (struct like)"))
+		match self.decoder.header()? {
+			Header::Simple(SIMPLE_UNDEFINED) => visitor.visit_unit(),
+			_ => Err(self.unexpected("unit (struct like)")),
 		}
 	}
 
 	#[inline]
-	fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+	fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
 	where
 		V: de::Visitor<'de>,
 	{
+		if name == crate::raw::TOKEN {
+			return self.deserialize_raw(visitor);
+		}
+		#[cfg(feature = "bytes")]
+		if name == crate::buf::TOKEN {
+			return self.deserialize_shared(visitor);
+		}
 		visitor.visit_newtype_struct(self)
 	}
 
@@ -544,20 +396,13 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let size: usize = match self.peek_and_consume()? {
-			n if HEADER_ARRAY_START <= n && n < HEADER_ARRAY_8 => (n & 0x1F) as usize,
-			HEADER_ARRAY_8 => (self.reader.read_u8()?) as usize,
-			HEADER_ARRAY_16 => (self.reader.read_u16()?) as usize,
-			HEADER_ARRAY_32 => usize::try_from(self.reader.read_u32()?)?,
-			HEADER_ARRAY_64 => usize::try_from(self.reader.read_u64()?)?,
-			HEADER_ARRAY_INFINITE => return Err(Error::Unsupported(HEADER_ARRAY_INFINITE)),
-			n => return Err(Error::Unexpected(n, "array")),
-		};
-		visitor.visit_seq(SeqAccess {
-			de: self,
-			len: size,
-		})
+		match self.decoder.header()? {
+			Header::Array(Some(len)) => self.visit_seq(len, visitor),
+			Header::Array(None) => Err(self.unsupported()),
+			_ => Err(self.unexpected("array")),
+		}
 	}
+
 	#[inline]
 	fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
 	where
@@ -584,19 +429,11 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		let size: usize = match self.peek_and_consume()? {
-			n if HEADER_MAP_START <= n && n < HEADER_MAP_8 => (n & 0x1F) as usize,
-			HEADER_MAP_8 => (self.reader.read_u8()?) as usize,
-			HEADER_MAP_16 => (self.reader.read_u16()?) as usize,
-			HEADER_MAP_32 => usize::try_from(self.reader.read_u32()?)?,
-			HEADER_MAP_64 => usize::try_from(self.reader.read_u64()?)?,
-			HEADER_MAP_INFINITE => return Err(Error::Unsupported(HEADER_MAP_INFINITE)),
-			n => return Err(Error::Unexpected(n, "map")),
-		};
-		visitor.visit_map(MapAccess {
-			de: self,
-			len: size,
-		})
+		match self.decoder.header()? {
+			Header::Map(Some(len)) => self.visit_map(len, visitor),
+			Header::Map(None) => Err(self.unsupported()),
+			_ => Err(self.unexpected("map")),
+		}
 	}
 
 	#[inline]
@@ -622,15 +459,16 @@ where
 	where
 		V: de::Visitor<'de>,
 	{
-		match self.peek()? {
-			n if HEADER_TEXT_START <= n && n <= HEADER_TEXT_64 => {
-				visitor.visit_enum(VariantAccess { de: self })
-			}
-			n if n == (HEADER_MAP_START | 1) => {
-				self.consume();
-				visitor.visit_enum(VariantAccess { de: self })
+		match self.decoder.peek()? {
+			Header::Text(Some(_)) => visitor.visit_enum(VariantAccess { de: self }),
+			Header::Map(Some(1)) => {
+				self.decoder.header()?;
+				self.decoder.enter()?;
+				let value = visitor.visit_enum(VariantAccess { de: self });
+				self.decoder.leave();
+				value
 			}
-			n => Err(Error::Unexpected(n, "enum (text or map(1))")),
+			_ => Err(self.unexpected("enum (text or map(1))")),
 		}
 	}
 
@@ -642,15 +480,22 @@ where
 		self.deserialize_str(visitor)
 	}
 
+	#[inline]
+	fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+	where
+		V: de::Visitor<'de>,
+	{
+		self.decoder.skip()?;
+		visitor.visit_unit()
+	}
+
 	#[inline]
 	fn is_human_readable(&self) -> bool {
 		false
 	}
 
 	serde::forward_to_deserialize_any! {
-		/* bool i8 i16 i32 i64 */ i128 /* u8 u16 u32 u64 */ u128 /* f32 f64 */
-		/* unit unit_struct seq tuple tuple_struct  struct map identifier */ ignored_any
-		/* char str string bytes byte_buf enum newtype_struct option */
+		i128 u128
 	}
 }
 
@@ -711,7 +556,7 @@ where
 	where
 		V: de::DeserializeSeed<'de>,
 	{
-		Ok(seed.deserialize(&mut *self.de)?)
+		seed.deserialize(&mut *self.de)
 	}
 
 	#[inline]
@@ -776,3 +621,138 @@ where
 		de::Deserializer::deserialize_map(&mut *self.de, visitor)
 	}
 }
+
+/// An iterator that deserializes a CBOR sequence (RFC 8742),
+/// one data item after another.
+///
+/// The iteration ends cleanly when the input is exhausted between
+/// two items. An input ending in the middle of an item yields
+/// an `Error::Eof`. The iterator is fused after the first error.
+pub struct StreamDeserializer<'de, R, T> {
+	de: Deserializer<R>,
+	offset: usize,
+	failed: bool,
+	output: PhantomData<T>,
+	lifetime: PhantomData<&'de ()>,
+}
+
+impl<'de, R, T> StreamDeserializer<'de, R, T>
+where
+	R: Reader<'de>,
+	T: de::Deserialize<'de>,
+{
+	pub fn new(de: Deserializer<R>) -> Self {
+		StreamDeserializer {
+			offset: de.offset(),
+			de,
+			failed: false,
+			output: PhantomData,
+			lifetime: PhantomData,
+		}
+	}
+
+	/// Offset at which the last yielded item (or error) started.
+	pub fn item_offset(&self) -> usize {
+		self.offset
+	}
+
+	/// Offset of the first byte not consumed yet.
+	pub fn byte_offset(&self) -> usize {
+		self.de.offset()
+	}
+}
+
+impl<'de, R, T> Iterator for StreamDeserializer<'de, R, T>
+where
+	R: Reader<'de>,
+	T: de::Deserialize<'de>,
+{
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.failed {
+			return None;
+		}
+		self.offset = self.de.offset();
+		match self.de.decoder.peek_initial() {
+			Ok(_) => {}
+			Err(Error::Eof) => return None,
+			Err(error) => {
+				self.failed = true;
+				return Some(Err(error));
+			}
+		}
+		let value = de::Deserialize::deserialize(&mut self.de);
+		self.failed = value.is_err();
+		Some(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stream(bytes: &[u8]) -> StreamDeserializer<'_, SliceReader<'_>, u32> {
+		Deserializer::new(SliceReader::new(bytes)).into_stream()
+	}
+
+	#[test]
+	fn stream_ends_cleanly_between_items() {
+		let mut items = stream(&[0x01, 0x18, 0x64, 0x02]);
+		assert_eq!(items.next().unwrap().unwrap(), 1);
+		assert_eq!(items.item_offset(), 0);
+		assert_eq!(items.next().unwrap().unwrap(), 100);
+		assert_eq!(items.item_offset(), 1);
+		assert_eq!(items.byte_offset(), 3);
+		assert_eq!(items.next().unwrap().unwrap(), 2);
+		assert!(items.next().is_none());
+		assert_eq!(items.byte_offset(), 4);
+	}
+
+	#[test]
+	fn stream_of_nothing() {
+		assert!(stream(&[]).next().is_none());
+	}
+
+	#[test]
+	fn stream_truncated_in_an_item() {
+		let mut items = stream(&[0x01, 0x19, 0x01]);
+		assert_eq!(items.next().unwrap().unwrap(), 1);
+		assert!(matches!(items.next(), Some(Err(Error::Eof))));
+		assert_eq!(items.item_offset(), 1);
+		assert!(items.next().is_none());
+	}
+
+	#[test]
+	fn stream_fused_after_an_error() {
+		let mut items = stream(&[0x01, 0x61, 0x61, 0x02]);
+		assert_eq!(items.next().unwrap().unwrap(), 1);
+		assert!(matches!(items.next(), Some(Err(Error::Unexpected(0x61, _)))));
+		assert!(items.next().is_none());
+	}
+
+	#[test]
+	fn stream_offsets_of_any_reader() {
+		/// A reader implementing only what is required.
+		struct Minimal<'a>(&'a [u8]);
+
+		impl<'a> Reader<'a> for Minimal<'a> {
+			fn read_bytes<'b>(&'b mut self, size: usize) -> Result<EitherLifetime<'b, 'a>> {
+				if size > self.0.len() {
+					return Err(Error::Eof);
+				}
+				let (bytes, rest) = self.0.split_at(size);
+				self.0 = rest;
+				Ok(EitherLifetime::Other(bytes))
+			}
+		}
+
+		let mut items = Deserializer::new(Minimal(&[0x01, 0x19, 0x01, 0x00, 0x02])).into_stream::<u32>();
+		assert_eq!(items.next().unwrap().unwrap(), 1);
+		assert_eq!(items.next().unwrap().unwrap(), 256);
+		assert_eq!(items.item_offset(), 1);
+		assert_eq!(items.byte_offset(), 4);
+		assert_eq!(items.next().unwrap().unwrap(), 2);
+		assert_eq!(items.byte_offset(), 5);
+	}
+}
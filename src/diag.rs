@@ -0,0 +1,440 @@
+use crate::cbor::*;
+use crate::decode::*;
+use crate::encode::{shortest_float_info, shortest_info};
+use crate::error::*;
+use crate::read::*;
+use crate::value::{has_next, payload, Value};
+use crate::Result;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::{self, Write};
+
+/// Print the single data item filling `bytes` in extended diagnostic
+/// notation (RFC 8949 section 8), on one line or indented over several.
+pub(crate) fn print(bytes: &[u8], pretty: bool) -> Result<String> {
+	let mut decoder = Decoder::new(SliceReader::new(bytes));
+	let mut printer = Printer::new(pretty);
+	printer.item(&mut decoder)?;
+	decoder.end()?;
+	Ok(printer.into_string())
+}
+
+/// The encoding indicator of an argument that is not encoded
+/// in the shortest form.
+fn indicator(info: u8, argument: u64) -> &'static str {
+	if info == shortest_info(argument) {
+		""
+	} else {
+		width_indicator(info)
+	}
+}
+
+/// The encoding indicator of a float that would fit in a smaller width.
+fn float_indicator(info: u8, value: f64) -> &'static str {
+	if info == shortest_float_info(value) {
+		""
+	} else {
+		width_indicator(info)
+	}
+}
+
+fn width_indicator(info: u8) -> &'static str {
+	match info {
+		SIZE_8 => "_0",
+		SIZE_16 => "_1",
+		SIZE_32 => "_2",
+		SIZE_64 => "_3",
+		_ => "_i",
+	}
+}
+
+/// What is left to print of a `Value`, the next step last.
+enum Task<'a> {
+	Value(&'a Value),
+	/// Separate the item at this index from the previous one.
+	Next(usize),
+	Push(&'static str),
+	/// Close a container of this many items.
+	Close(char, usize),
+}
+
+/// Builds diagnostic notation in a string.
+pub(crate) struct Printer {
+	out: String,
+	pretty: bool,
+	depth: usize,
+}
+
+impl Printer {
+	pub(crate) fn new(pretty: bool) -> Self {
+		Printer {
+			out: String::new(),
+			pretty,
+			depth: 0,
+		}
+	}
+
+	pub(crate) fn into_string(self) -> String {
+		self.out
+	}
+
+	/// Print the next data item of `decoder`, with the encoding indicators
+	/// of the arguments not encoded in the shortest form. Containers and
+	/// tags nested deeper than the depth limit of `decoder` are rejected.
+	fn item<'r, R: Reader<'r>>(&mut self, decoder: &mut Decoder<R>) -> Result<()> {
+		let header = decoder.header()?;
+		let info = decoder.initial() & 0x1F;
+		match header {
+			Header::Positive(value) => self.write(format_args!("{}{}", value, indicator(info, value))),
+			Header::Negative(value) => {
+				self.write(format_args!("{}{}", -1 - value as i128, indicator(info, value)))
+			}
+			Header::Bytes(Some(len)) => {
+				self.bytes(payload(decoder, len)?);
+				self.out.push_str(indicator(info, len));
+			}
+			Header::Text(Some(len)) => {
+				self.text(core::str::from_utf8(payload(decoder, len)?)?);
+				self.out.push_str(indicator(info, len));
+			}
+			Header::Bytes(None) | Header::Text(None) => {
+				let empty = if let Header::Bytes(_) = header { "''_" } else { "\"\"_" };
+				let major = decoder.initial() >> 5;
+				if let Header::Break = decoder.peek()? {
+					decoder.header()?;
+					self.out.push_str(empty);
+					return Ok(());
+				}
+				self.open('(', "_");
+				let mut count = 0;
+				while !at_break(decoder)? {
+					self.next(count);
+					let initial = decoder.peek_initial()?;
+					if initial >> 5 != major || initial & 0x1F == SIZE_INFINITE {
+						return Err(Error::Unexpected(initial, "definite string chunk"));
+					}
+					self.item(decoder)?;
+					count += 1;
+				}
+				self.close(')', count);
+			}
+			Header::Array(len) => {
+				decoder.enter()?;
+				self.open('[', container_indicator(info, len));
+				let mut count = 0;
+				while has_next(decoder, count, len)? {
+					self.next(count);
+					self.item(decoder)?;
+					count += 1;
+				}
+				self.close(']', count);
+				decoder.leave();
+			}
+			Header::Map(len) => {
+				decoder.enter()?;
+				self.open('{', container_indicator(info, len));
+				let mut count = 0;
+				while has_next(decoder, count, len)? {
+					self.next(count);
+					self.item(decoder)?;
+					self.out.push_str(": ");
+					self.item(decoder)?;
+					count += 1;
+				}
+				self.close('}', count);
+				decoder.leave();
+			}
+			Header::Tag(tag) => {
+				decoder.enter()?;
+				self.write(format_args!("{}{}(", tag, indicator(info, tag)));
+				self.item(decoder)?;
+				self.out.push(')');
+				decoder.leave();
+			}
+			Header::Simple(value) => self.simple(value),
+			Header::Float(value) => {
+				self.float(value);
+				self.out.push_str(float_indicator(info, value));
+			}
+			Header::Break => return Err(Error::Unexpected(HEADER_BREAK, "any other header")),
+		}
+		Ok(())
+	}
+
+	/// Print `value`, always in the shortest form. Nested values are
+	/// walked with an explicit stack, so that any nesting can be printed.
+	pub(crate) fn value(&mut self, value: &Value) {
+		let mut tasks = vec![Task::Value(value)];
+		while let Some(task) = tasks.pop() {
+			let value = match task {
+				Task::Value(value) => value,
+				Task::Next(index) => {
+					self.next(index);
+					continue;
+				}
+				Task::Push(text) => {
+					self.out.push_str(text);
+					continue;
+				}
+				Task::Close(bracket, count) => {
+					self.close(bracket, count);
+					continue;
+				}
+			};
+			match value {
+				Value::UInt(value) => self.write(format_args!("{}", value)),
+				Value::NegInt(value) => self.write(format_args!("{}", -1 - *value as i128)),
+				Value::Bytes(value) => self.bytes(value),
+				Value::Text(value) => self.text(value),
+				Value::Array(items) => {
+					self.open('[', "");
+					tasks.push(Task::Close(']', items.len()));
+					for (index, item) in items.iter().enumerate().rev() {
+						tasks.push(Task::Value(item));
+						tasks.push(Task::Next(index));
+					}
+				}
+				Value::Map(pairs) => {
+					self.open('{', "");
+					tasks.push(Task::Close('}', pairs.len()));
+					for (index, (key, value)) in pairs.iter().enumerate().rev() {
+						tasks.push(Task::Value(value));
+						tasks.push(Task::Push(": "));
+						tasks.push(Task::Value(key));
+						tasks.push(Task::Next(index));
+					}
+				}
+				Value::Tag(tag, value) => {
+					self.write(format_args!("{}(", tag));
+					tasks.push(Task::Push(")"));
+					tasks.push(Task::Value(value));
+				}
+				Value::Bool(false) => self.simple(SIMPLE_FALSE),
+				Value::Bool(true) => self.simple(SIMPLE_TRUE),
+				Value::Null => self.simple(SIMPLE_NULL),
+				Value::Undefined => self.simple(SIMPLE_UNDEFINED),
+				Value::Simple(value) => self.simple(*value),
+				Value::Float(value) => self.float(*value),
+			}
+		}
+	}
+
+	#[inline]
+	fn write(&mut self, args: fmt::Arguments) {
+		// Writing to a `String` cannot fail.
+		let _ = self.out.write_fmt(args);
+	}
+
+	fn open(&mut self, bracket: char, indicator: &str) {
+		self.out.push(bracket);
+		self.out.push_str(indicator);
+		self.depth += 1;
+	}
+
+	/// Separate the item at `index` from the previous one.
+	fn next(&mut self, index: usize) {
+		if index > 0 {
+			self.out.push(',');
+		}
+		if self.pretty {
+			self.newline();
+		} else if index > 0 || !self.out.ends_with(['[', '{', '(']) {
+			self.out.push(' ');
+		}
+	}
+
+	fn close(&mut self, bracket: char, count: usize) {
+		self.depth -= 1;
+		if self.pretty && count > 0 {
+			self.newline();
+		} else if count == 0 && self.out.ends_with('_') {
+			self.out.push(' ');
+		}
+		self.out.push(bracket);
+	}
+
+	fn newline(&mut self) {
+		self.out.push('\n');
+		for _ in 0..self.depth {
+			self.out.push_str("  ");
+		}
+	}
+
+	fn bytes(&mut self, bytes: &[u8]) {
+		self.out.push_str("h'");
+		for byte in bytes {
+			self.write(format_args!("{:02x}", byte));
+		}
+		self.out.push('\'');
+	}
+
+	fn text(&mut self, text: &str) {
+		self.out.push('"');
+		for c in text.chars() {
+			match c {
+				'"' => self.out.push_str("\\\""),
+				'\\' => self.out.push_str("\\\\"),
+				'\n' => self.out.push_str("\\n"),
+				'\r' => self.out.push_str("\\r"),
+				'\t' => self.out.push_str("\\t"),
+				c if c.is_control() => self.write(format_args!("\\u{:04x}", c as u32)),
+				c => self.out.push(c),
+			}
+		}
+		self.out.push('"');
+	}
+
+	fn simple(&mut self, value: u8) {
+		match value {
+			SIMPLE_FALSE => self.out.push_str("false"),
+			SIMPLE_TRUE => self.out.push_str("true"),
+			SIMPLE_NULL => self.out.push_str("null"),
+			SIMPLE_UNDEFINED => self.out.push_str("undefined"),
+			value => self.write(format_args!("simple({})", value)),
+		}
+	}
+
+	fn float(&mut self, value: f64) {
+		if value.is_nan() {
+			self.out.push_str("NaN");
+		} else if value.is_infinite() {
+			self.out.push_str(if value > 0.0 { "Infinity" } else { "-Infinity" });
+		} else {
+			self.write(format_args!("{:?}", value));
+		}
+	}
+}
+
+fn container_indicator(info: u8, len: Option<u64>) -> &'static str {
+	match len {
+		Some(len) => indicator(info, len),
+		None => "_",
+	}
+}
+
+/// Whether the next header is a break, consuming it if so.
+fn at_break<'r, R: Reader<'r>>(decoder: &mut Decoder<R>) -> Result<bool> {
+	match decoder.peek()? {
+		Header::Break => {
+			decoder.header()?;
+			Ok(true)
+		}
+		_ => Ok(false),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::boxed::Box;
+	use alloc::string::ToString;
+	use alloc::vec;
+
+	fn diag(bytes: &[u8]) -> String {
+		print(bytes, false).unwrap()
+	}
+
+	#[test]
+	fn integers() {
+		assert_eq!(diag(&[0x17]), "23");
+		assert_eq!(diag(&[0x18, 0x18]), "24");
+		assert_eq!(diag(&[0x18, 0x17]), "23_0");
+		assert_eq!(diag(&[0x19, 0x00, 0x01]), "1_1");
+		assert_eq!(diag(&[0x1A, 0x00, 0x00, 0x01, 0x00]), "256_2");
+		assert_eq!(diag(&[0x1B, 0, 0, 0, 0, 0, 0, 0, 0x01]), "1_3");
+		assert_eq!(diag(&[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), "-18446744073709551616");
+		assert_eq!(diag(&[0x38, 0x00]), "-1_0");
+	}
+
+	#[test]
+	fn strings() {
+		assert_eq!(diag(&[0x43, 0x01, 0xAB, 0xFF]), "h'01abff'");
+		assert_eq!(diag(&[0x58, 0x01, 0x00]), "h'00'_0");
+		assert_eq!(diag(&[0x65, b'a', b'"', b'\\', b'\n', 0x01]), "\"a\\\"\\\\\\n\\u0001\"");
+		assert_eq!(diag(&[0x79, 0x00, 0x01, b'a']), "\"a\"_1");
+		assert_eq!(diag(&[0x5F, 0x41, 0x01, 0x42, 0x02, 0x03, 0xFF]), "(_ h'01', h'0203')");
+		assert_eq!(diag(&[0x7F, 0x61, b'a', 0xFF]), "(_ \"a\")");
+		assert_eq!(diag(&[0x5F, 0xFF]), "''_");
+		assert_eq!(diag(&[0x7F, 0xFF]), "\"\"_");
+		assert!(matches!(print(&[0x5F, 0x61, b'a', 0xFF], false), Err(Error::Unexpected(0x61, _))));
+		assert!(matches!(print(&[0x5F, 0x5F, 0xFF, 0xFF], false), Err(Error::Unexpected(0x5F, _))));
+	}
+
+	#[test]
+	fn containers() {
+		assert_eq!(diag(&[0x80]), "[]");
+		assert_eq!(diag(&[0x82, 0x01, 0x81, 0x02]), "[1, [2]]");
+		assert_eq!(diag(&[0x98, 0x01, 0x01]), "[_0 1]");
+		assert_eq!(diag(&[0x9F, 0x01, 0x02, 0xFF]), "[_ 1, 2]");
+		assert_eq!(diag(&[0x9F, 0xFF]), "[_ ]");
+		assert_eq!(diag(&[0xA2, 0x01, 0x61, b'a', 0x80, 0xA0]), "{1: \"a\", []: {}}");
+		assert_eq!(diag(&[0xBF, 0x01, 0x02, 0xFF]), "{_ 1: 2}");
+		assert_eq!(diag(&[0xB9, 0x00, 0x00]), "{_1}");
+		assert_eq!(diag(&[0xC1, 0x00]), "1(0)");
+		assert_eq!(diag(&[0xD8, 0x01, 0x00]), "1_0(0)");
+		assert!(matches!(print(&[0x82, 0x01], false), Err(Error::Eof)));
+		assert!(matches!(print(&[0xFF], false), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(print(&[0x01, 0x02], false), Err(Error::TrailingData(1))));
+	}
+
+	#[test]
+	fn simple_and_floats() {
+		assert_eq!(diag(&[0x84, 0xF4, 0xF5, 0xF6, 0xF7]), "[false, true, null, undefined]");
+		assert_eq!(diag(&[0xF0]), "simple(16)");
+		assert_eq!(diag(&[0xF8, 0xFF]), "simple(255)");
+		assert_eq!(diag(&[0xF9, 0x3E, 0x00]), "1.5");
+		assert_eq!(diag(&[0xFA, 0x3F, 0xC0, 0x00, 0x00]), "1.5_2");
+		assert_eq!(diag(&[0xFB, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0]), "1.5_3");
+		assert_eq!(diag(&[0xFA, 0x3D, 0xCC, 0xCC, 0xCD]), "0.10000000149011612");
+		assert_eq!(diag(&[0xFB, 0x3F, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]), "0.1");
+		assert_eq!(diag(&[0xF9, 0x7E, 0x00]), "NaN");
+		assert_eq!(diag(&[0xFA, 0x7F, 0xC0, 0x00, 0x00]), "NaN_2");
+		assert_eq!(diag(&[0xF9, 0x7C, 0x00]), "Infinity");
+		assert_eq!(diag(&[0xF9, 0xFC, 0x00]), "-Infinity");
+	}
+
+	#[test]
+	fn pretty() {
+		// {"a": [1, []], "b": 2(h'')}
+		let bytes = [0xA2, 0x61, b'a', 0x82, 0x01, 0x80, 0x61, b'b', 0xC2, 0x40];
+		assert_eq!(print(&bytes, true).unwrap(), "{\n  \"a\": [\n    1,\n    []\n  ],\n  \"b\": 2(h'')\n}");
+		assert_eq!(print(&[0x9F, 0xFF], true).unwrap(), "[_ ]");
+		assert_eq!(print(&[0x5F, 0x40, 0xFF], true).unwrap(), "(_\n  h''\n)");
+	}
+
+	#[test]
+	fn values() {
+		let value = Value::Map(vec![
+			(Value::Text("a".to_string()), Value::Array(vec![Value::UInt(1), Value::NegInt(1)])),
+			(Value::Bytes(vec![0xAB]), Value::Tag(1, Box::new(Value::Float(1.5)))),
+			(Value::Simple(16), Value::Undefined),
+		]);
+		assert_eq!(value.to_string(), "{\"a\": [1, -2], h'ab': 1(1.5), simple(16): undefined}");
+		assert_eq!(
+			alloc::format!("{:#}", value),
+			"{\n  \"a\": [\n    1,\n    -2\n  ],\n  h'ab': 1(1.5),\n  simple(16): undefined\n}"
+		);
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let mut bytes = vec![0x81; DEPTH_LIMIT];
+		bytes.push(0x00);
+		assert!(print(&bytes, false).is_ok());
+		bytes.insert(0, 0xC1);
+		assert!(matches!(print(&bytes, false), Err(Error::Limit(LimitKind::Depth))));
+		let bytes = vec![0xA1; 100_000];
+		assert!(matches!(print(&bytes, false), Err(Error::Limit(LimitKind::Depth))));
+	}
+
+	#[test]
+	fn values_of_any_depth() {
+		let mut value = Value::Null;
+		for _ in 0..DEPTH_LIMIT * 10 {
+			value = Value::Tag(0, Box::new(Value::Array(vec![value])));
+		}
+		let text = value.to_string();
+		assert_eq!(text.len(), DEPTH_LIMIT * 10 * 5 + 4);
+		assert!(text.starts_with("0([0([") && text.contains("0([null])])") && text.ends_with("])])"));
+	}
+}
@@ -0,0 +1,784 @@
+use crate::cbor::*;
+use crate::decode::DEPTH_LIMIT;
+use crate::encode::*;
+use crate::error::*;
+use crate::Result;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Encode the single data item written in `text` in extended diagnostic
+/// notation.
+pub(crate) fn parse(text: &str) -> Result<Vec<u8>> {
+	let mut parser = Parser {
+		text,
+		pos: 0,
+		out: Vec::new(),
+		depth: 0,
+	};
+	parser.item()?;
+	parser.skip_space()?;
+	if parser.pos < text.len() {
+		return Err(parser.error("end of input"));
+	}
+	Ok(parser.out)
+}
+
+/// An encoding indicator following a number, a string or an opening bracket.
+#[derive(Clone, Copy)]
+enum Indicator {
+	/// `_`, an indefinite length.
+	Indefinite,
+	/// `_i`, the argument in the initial byte.
+	Immediate,
+	/// `_0` to `_3`, the additional information of the argument.
+	Width(u8),
+}
+
+struct Parser<'a> {
+	text: &'a str,
+	pos: usize,
+	out: Vec<u8>,
+	/// Items being parsed, the outer ones holding the inner ones.
+	depth: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error(&self, expected: &'static str) -> Error {
+		self.error_at(self.pos, expected)
+	}
+
+	fn error_at(&self, pos: usize, expected: &'static str) -> Error {
+		let before = &self.text[..pos];
+		let line = before.matches('\n').count() + 1;
+		let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+		Error::Parse(line, column, expected)
+	}
+
+	#[inline]
+	fn peek(&self) -> Option<u8> {
+		self.text.as_bytes().get(self.pos).copied()
+	}
+
+	#[inline]
+	fn rest(&self) -> &'a str {
+		&self.text[self.pos..]
+	}
+
+	fn eat(&mut self, c: u8) -> bool {
+		if self.peek() == Some(c) {
+			self.pos += 1;
+			true
+		} else {
+			false
+		}
+	}
+
+	fn expect(&mut self, c: u8, expected: &'static str) -> Result<()> {
+		self.skip_space()?;
+		if self.eat(c) {
+			Ok(())
+		} else {
+			Err(self.error(expected))
+		}
+	}
+
+	/// Skip whitespace, `# ...` comments up to the end of the line
+	/// and `/ ... /` comments.
+	fn skip_space(&mut self) -> Result<()> {
+		loop {
+			match self.peek() {
+				Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') => self.pos += 1,
+				Some(b'#') => {
+					self.pos = self.rest().find('\n').map_or(self.text.len(), |end| self.pos + end);
+				}
+				Some(b'/') => {
+					let start = self.pos;
+					match self.rest()[1..].find('/') {
+						Some(end) => self.pos += end + 2,
+						None => return Err(self.error_at(start, "end of comment")),
+					}
+				}
+				_ => return Ok(()),
+			}
+		}
+	}
+
+	/// Parse the encoding indicator following the current position, if any.
+	fn indicator(&mut self) -> Result<Option<Indicator>> {
+		if !self.eat(b'_') {
+			return Ok(None);
+		}
+		let indicator = match self.peek() {
+			Some(b'i') => Indicator::Immediate,
+			Some(b'0') => Indicator::Width(SIZE_8),
+			Some(b'1') => Indicator::Width(SIZE_16),
+			Some(b'2') => Indicator::Width(SIZE_32),
+			Some(b'3') => Indicator::Width(SIZE_64),
+			_ => return Ok(Some(Indicator::Indefinite)),
+		};
+		self.pos += 1;
+		Ok(Some(indicator))
+	}
+
+	/// Parse a data item, rejecting containers and tags nested deeper
+	/// than `DEPTH_LIMIT`.
+	fn item(&mut self) -> Result<()> {
+		if self.depth > DEPTH_LIMIT {
+			return Err(Error::Limit(LimitKind::Depth));
+		}
+		self.depth += 1;
+		let result = self.value();
+		self.depth -= 1;
+		result
+	}
+
+	fn value(&mut self) -> Result<()> {
+		self.skip_space()?;
+		let start = self.pos;
+		match self.peek() {
+			Some(b'[') => {
+				self.pos += 1;
+				self.container(start, MAJOR_ARRAY, b']')
+			}
+			Some(b'{') => {
+				self.pos += 1;
+				self.container(start, MAJOR_MAP, b'}')
+			}
+			Some(b'(') => self.indefinite_string(),
+			Some(b'_') => {
+				// `_ [...]` and `_ {...}` are read as `[_ ...]` and `{_ ...}`.
+				self.pos += 1;
+				self.skip_space()?;
+				let (major, close) = match self.peek() {
+					Some(b'[') => (MAJOR_ARRAY, b']'),
+					Some(b'{') => (MAJOR_MAP, b'}'),
+					_ => return Err(self.error("`[` or `{` of an indefinite container")),
+				};
+				self.pos += 1;
+				self.container_with(start, major, close, Some(Indicator::Indefinite))
+			}
+			Some(b'"') => {
+				let text = self.quoted(b'"')?;
+				self.string(start, MAJOR_TEXT, text.as_bytes())
+			}
+			Some(b'\'') => {
+				let text = self.quoted(b'\'')?;
+				self.string(start, MAJOR_BYTE, text.as_bytes())
+			}
+			Some(b'<') if self.rest().starts_with("<<") => self.embedded(),
+			Some(c) if c.is_ascii_digit() || c == b'-' || c == b'+' => self.number(),
+			Some(c) if c.is_ascii_alphabetic() => self.word(),
+			_ => Err(self.error("a data item")),
+		}
+	}
+
+	/// Parse an array or a map up to `close`, its opening bracket at `start`
+	/// being already consumed.
+	fn container(&mut self, start: usize, major: u8, close: u8) -> Result<()> {
+		let indicator = self.indicator()?;
+		self.container_with(start, major, close, indicator)
+	}
+
+	fn container_with(&mut self, start: usize, major: u8, close: u8, indicator: Option<Indicator>) -> Result<()> {
+		let header = self.out.len();
+		let mut count = 0u64;
+		loop {
+			self.skip_space()?;
+			if self.eat(close) {
+				break;
+			}
+			if count > 0 {
+				self.expect(b',', "`,` or the end of the container")?;
+				self.skip_space()?;
+				if self.eat(close) {
+					break;
+				}
+			}
+			self.item()?;
+			if major == MAJOR_MAP {
+				self.expect(b':', "`:`")?;
+				self.item()?;
+			}
+			count += 1;
+		}
+		match indicator {
+			Some(Indicator::Indefinite) => {
+				self.out.insert(header, major << 5 | SIZE_INFINITE);
+				self.out.push(HEADER_BREAK);
+			}
+			indicator => {
+				let bytes = self.header(start, major, count, indicator)?;
+				self.out.splice(header..header, bytes);
+			}
+		}
+		Ok(())
+	}
+
+	/// Parse `(_ chunk, ...)`, an indefinite byte or text string.
+	fn indefinite_string(&mut self) -> Result<()> {
+		self.pos += 1;
+		self.expect(b'_', "`_` of an indefinite string")?;
+		let header = self.out.len();
+		let mut major = None;
+		loop {
+			self.skip_space()?;
+			if self.eat(b')') {
+				break;
+			}
+			if major.is_some() {
+				self.expect(b',', "`,` or `)`")?;
+			}
+			self.skip_space()?;
+			let start = self.pos;
+			let chunk = self.out.len();
+			self.item()?;
+			let initial = self.out[chunk];
+			match major {
+				_ if initial & 0x1F == SIZE_INFINITE => {}
+				None if initial >> 5 == MAJOR_BYTE || initial >> 5 == MAJOR_TEXT => {
+					major = Some(initial >> 5);
+					continue;
+				}
+				Some(major) if initial >> 5 == major => continue,
+				_ => {}
+			}
+			return Err(self.error_at(start, "definite string chunk"));
+		}
+		let major = match major {
+			Some(major) => major,
+			None => return Err(self.error_at(header, "a chunk, use ''_ or \"\"_ when empty")),
+		};
+		self.out.insert(header, major << 5 | SIZE_INFINITE);
+		self.out.push(HEADER_BREAK);
+		Ok(())
+	}
+
+	/// Parse `<< item, ... >>`, a byte string holding encoded data items.
+	fn embedded(&mut self) -> Result<()> {
+		let start = self.pos;
+		self.pos += 2;
+		let outer = core::mem::take(&mut self.out);
+		let mut first = true;
+		loop {
+			self.skip_space()?;
+			if self.rest().starts_with(">>") {
+				self.pos += 2;
+				break;
+			}
+			if !first {
+				self.expect(b',', "`,` or `>>`")?;
+			}
+			self.item()?;
+			first = false;
+		}
+		let inner = core::mem::replace(&mut self.out, outer);
+		self.string(start, MAJOR_BYTE, &inner)
+	}
+
+	/// Write a definite or empty indefinite string, followed by its
+	/// optional encoding indicator.
+	fn string(&mut self, start: usize, major: u8, content: &[u8]) -> Result<()> {
+		match self.indicator()? {
+			Some(Indicator::Indefinite) if content.is_empty() => {
+				self.out.push(major << 5 | SIZE_INFINITE);
+				self.out.push(HEADER_BREAK);
+			}
+			Some(Indicator::Indefinite) => {
+				return Err(self.error_at(start, "an empty string before `_`"));
+			}
+			indicator => {
+				let header = self.header(start, major, content.len() as u64, indicator)?;
+				self.out.extend_from_slice(&header);
+				self.out.extend_from_slice(content);
+			}
+		}
+		Ok(())
+	}
+
+	/// Parse a string between `quote`s, with JSON escapes.
+	fn quoted(&mut self, quote: u8) -> Result<String> {
+		let start = self.pos;
+		self.pos += 1;
+		let mut text = String::new();
+		loop {
+			let rest = self.rest();
+			let end = match rest.find([quote as char, '\\']) {
+				Some(end) => end,
+				None => return Err(self.error_at(start, "end of string")),
+			};
+			text.push_str(&rest[..end]);
+			self.pos += end + 1;
+			if rest.as_bytes()[end] == quote {
+				return Ok(text);
+			}
+			let escape = self.pos - 1;
+			let c = match self.peek() {
+				Some(b'"') => '"',
+				Some(b'\'') => '\'',
+				Some(b'\\') => '\\',
+				Some(b'/') => '/',
+				Some(b'b') => '\u{8}',
+				Some(b'f') => '\u{c}',
+				Some(b'n') => '\n',
+				Some(b'r') => '\r',
+				Some(b't') => '\t',
+				Some(b'u') => {
+					self.pos += 1;
+					let high = self.hex4(escape)?;
+					let code = if (0xD800..0xDC00).contains(&high) && self.rest().starts_with("\\u") {
+						let second = self.pos;
+						self.pos += 2;
+						let low = self.hex4(second)?;
+						if !(0xDC00..0xE000).contains(&low) {
+							return Err(self.error_at(second, "a low surrogate `\\u` escape"));
+						}
+						0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+					} else {
+						high
+					};
+					text.push(char::from_u32(code).ok_or_else(|| self.error_at(escape, "a valid `\\u` escape"))?);
+					continue;
+				}
+				_ => return Err(self.error_at(escape, "a valid escape")),
+			};
+			self.pos += 1;
+			text.push(c);
+		}
+	}
+
+	fn hex4(&mut self, escape: usize) -> Result<u32> {
+		let digits = self.rest().get(..4).ok_or_else(|| self.error_at(escape, "a valid `\\u` escape"))?;
+		if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+			return Err(self.error_at(escape, "a valid `\\u` escape"));
+		}
+		let value = u32::from_str_radix(digits, 16).map_err(|_| self.error_at(escape, "a valid `\\u` escape"))?;
+		self.pos += 4;
+		Ok(value)
+	}
+
+	/// Parse an integer or a float with its optional encoding indicator.
+	fn number(&mut self) -> Result<()> {
+		let start = self.pos;
+		let negative = self.eat(b'-');
+		if !negative {
+			self.eat(b'+');
+		}
+		if self.rest().starts_with("Infinity") {
+			self.pos += "Infinity".len();
+			let value = if negative { f64::NEG_INFINITY } else { f64::INFINITY };
+			let indicator = self.indicator()?;
+			return self.float(start, value, indicator);
+		}
+		let digits = self.pos;
+		let radix = match self.rest().get(..2) {
+			Some("0x") | Some("0X") => 16,
+			Some("0o") | Some("0O") => 8,
+			Some("0b") | Some("0B") => 2,
+			_ => 10,
+		};
+		if radix != 10 {
+			self.pos += 2;
+		}
+		let mut float = false;
+		while let Some(c) = self.peek() {
+			match c {
+				b'.' if radix == 10 => float = true,
+				b'e' | b'E' if radix == 10 => {
+					float = true;
+					if let Some(b'+') | Some(b'-') = self.text.as_bytes().get(self.pos + 1) {
+						self.pos += 1;
+					}
+				}
+				c if (c as char).is_digit(radix) => {}
+				_ => break,
+			}
+			self.pos += 1;
+		}
+		let token = &self.text[digits..self.pos];
+		let indicator = self.indicator()?;
+		if float {
+			let value: f64 = token.parse().map_err(|_| self.error_at(start, "a number"))?;
+			return self.float(start, if negative { -value } else { value }, indicator);
+		}
+		let token = if radix == 10 { token } else { &token[2..] };
+		let value = u128::from_str_radix(token, radix).map_err(|_| self.error_at(start, "a number"))?;
+		let (major, argument) = match (negative, value) {
+			(_, 0) => (MAJOR_POSITIVE, 0),
+			(false, value) => (MAJOR_POSITIVE, value),
+			(true, value) => (MAJOR_NEGATIVE, value - 1),
+		};
+		let argument = u64::try_from(argument).map_err(|_| self.error_at(start, "an integer within 64 bits"))?;
+		if self.peek() == Some(b'(') && !negative {
+			self.pos += 1;
+			let header = self.header(start, MAJOR_TAG, argument, indicator)?;
+			self.out.extend_from_slice(&header);
+			self.item()?;
+			return self.expect(b')', "`)` closing the tag");
+		}
+		let header = self.header(start, major, argument, indicator)?;
+		self.out.extend_from_slice(&header);
+		Ok(())
+	}
+
+	/// Parse a keyword, `simple(n)` or an application-prefixed string.
+	fn word(&mut self) -> Result<()> {
+		let start = self.pos;
+		let len = self.rest().find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(self.rest().len());
+		let word = &self.rest()[..len];
+		self.pos += len;
+		if self.peek() == Some(b'\'') {
+			return self.app_string(start, word);
+		}
+		match word {
+			"false" => self.out.push(HEADER_FALSE),
+			"true" => self.out.push(HEADER_TRUE),
+			"null" => self.out.push(HEADER_NULL),
+			"undefined" => self.out.push(HEADER_UNDEFINED),
+			"NaN" | "Infinity" => {
+				let value = if word == "NaN" { f64::NAN } else { f64::INFINITY };
+				let indicator = self.indicator()?;
+				return self.float(start, value, indicator);
+			}
+			"simple" => {
+				self.expect(b'(', "`(`")?;
+				self.skip_space()?;
+				let digits = self.pos;
+				let len = self.rest().find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest().len());
+				self.pos += len;
+				let value: u8 = self.text[digits..self.pos]
+					.parse()
+					.map_err(|_| self.error_at(digits, "a simple value up to 255"))?;
+				self.expect(b')', "`)`")?;
+				match value {
+					value if value < SIZE_8 => self.out.push(MAJOR_PRIMITIVE << 5 | value),
+					value if value < 32 => return Err(self.error_at(digits, "a simple value not reserved")),
+					value => self.out.extend_from_slice(&[HEADER_SIMPLE_8, value]),
+				}
+			}
+			_ => return Err(self.error_at(start, "a data item")),
+		}
+		Ok(())
+	}
+
+	/// Parse the string following an application prefix such as `h`.
+	fn app_string(&mut self, start: usize, prefix: &str) -> Result<()> {
+		let content = self.quoted(b'\'')?;
+		match prefix {
+			"h" => {
+				let bytes = self.hex(start, &content)?;
+				self.string(start, MAJOR_BYTE, &bytes)
+			}
+			"b64" => {
+				let bytes = base64(&content).ok_or_else(|| self.error_at(start, "base64 or base64url"))?;
+				self.string(start, MAJOR_BYTE, &bytes)
+			}
+			"dt" | "DT" => {
+				if prefix == "DT" {
+					self.out.push(HEADER_TAG_START | 1);
+				}
+				let (seconds, fraction) = date_time(&content).ok_or_else(|| self.error_at(start, "an RFC 3339 date and time"))?;
+				let indicator = self.indicator()?;
+				match fraction {
+					Some(fraction) => self.float(start, seconds as f64 + fraction, indicator),
+					None => {
+						let (major, argument) = if seconds < 0 {
+							(MAJOR_NEGATIVE, (-1 - seconds) as u64)
+						} else {
+							(MAJOR_POSITIVE, seconds as u64)
+						};
+						let header = self.header(start, major, argument, indicator)?;
+						self.out.extend_from_slice(&header);
+						Ok(())
+					}
+				}
+			}
+			_ => Err(self.error_at(start, "h, b64, dt or DT application prefix")),
+		}
+	}
+
+	/// Decode the hexadecimal content of `h''`, where whitespace
+	/// and comments are allowed.
+	fn hex(&self, start: usize, content: &str) -> Result<Vec<u8>> {
+		let mut bytes = Vec::new();
+		let mut high = None;
+		let mut chars = content.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				c if c.is_ascii_whitespace() => {}
+				'#' => while !matches!(chars.next(), Some('\n') | None) {},
+				'/' => {
+					if !chars.any(|c| c == '/') {
+						return Err(self.error_at(start, "end of comment"));
+					}
+				}
+				c => {
+					let digit = c.to_digit(16).ok_or_else(|| self.error_at(start, "hexadecimal digits"))? as u8;
+					match high.take() {
+						Some(high) => bytes.push(high << 4 | digit),
+						None => high = Some(digit),
+					}
+				}
+			}
+		}
+		match high {
+			Some(_) => Err(self.error_at(start, "an even number of hexadecimal digits")),
+			None => Ok(bytes),
+		}
+	}
+
+	/// Write a float in its shortest exact width, unless an indicator is given.
+	fn float(&mut self, start: usize, value: f64, indicator: Option<Indicator>) -> Result<()> {
+		let info = match indicator {
+			None => shortest_float_info(value),
+			Some(Indicator::Width(info)) if float_fits(value, info) => info,
+			_ => return Err(self.error_at(start, "a float fitting its encoding indicator")),
+		};
+		let mut buffer = [0u8; 9];
+		let len = encode_float(&mut buffer, value, info);
+		self.out.extend_from_slice(&buffer[..len]);
+		Ok(())
+	}
+
+	/// Encode a header, in the shortest form unless an indicator is given.
+	fn header(&self, start: usize, major: u8, argument: u64, indicator: Option<Indicator>) -> Result<Vec<u8>> {
+		let info = match indicator {
+			None => shortest_info(argument),
+			Some(Indicator::Immediate) if argument < SIZE_8 as u64 => argument as u8,
+			Some(Indicator::Width(info)) if fits(argument, info) => info,
+			_ => return Err(self.error_at(start, "an argument fitting its encoding indicator")),
+		};
+		let mut buffer = [0u8; 9];
+		let len = encode_header(&mut buffer, major, argument, info);
+		Ok(buffer[..len].to_vec())
+	}
+}
+
+/// Decode base64 or base64url, with or without padding, ignoring whitespace.
+fn base64(content: &str) -> Option<Vec<u8>> {
+	let mut bytes = Vec::new();
+	let mut buffer = 0u32;
+	let mut bits = 0;
+	let mut padding = false;
+	for c in content.chars() {
+		let value = match c {
+			'A'..='Z' => c as u32 - 'A' as u32,
+			'a'..='z' => c as u32 - 'a' as u32 + 26,
+			'0'..='9' => c as u32 - '0' as u32 + 52,
+			'+' | '-' => 62,
+			'/' | '_' => 63,
+			'=' => {
+				padding = true;
+				continue;
+			}
+			c if c.is_ascii_whitespace() => continue,
+			_ => return None,
+		};
+		if padding {
+			return None;
+		}
+		buffer = buffer << 6 | value;
+		bits += 6;
+		if bits >= 8 {
+			bits -= 8;
+			bytes.push((buffer >> bits) as u8);
+			buffer &= (1 << bits) - 1;
+		}
+	}
+	if bits >= 6 || buffer != 0 {
+		return None;
+	}
+	Some(bytes)
+}
+
+/// Parse an RFC 3339 date and time into seconds since the epoch,
+/// with the fraction of a second if any.
+fn date_time(text: &str) -> Option<(i64, Option<f64>)> {
+	let bytes = text.as_bytes();
+	let number = |range: core::ops::Range<usize>| -> Option<i64> {
+		let digits = text.get(range)?;
+		if digits.bytes().all(|c| c.is_ascii_digit()) {
+			digits.parse().ok()
+		} else {
+			None
+		}
+	};
+	if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't')
+		|| bytes[13] != b':' || bytes[16] != b':'
+	{
+		return None;
+	}
+	let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+	let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+	if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+		return None;
+	}
+	let mut end = 19;
+	let mut fraction = None;
+	if bytes[end] == b'.' {
+		let digits = bytes[end + 1..].iter().take_while(|c| c.is_ascii_digit()).count();
+		if digits == 0 {
+			return None;
+		}
+		fraction = Some(text[end..end + 1 + digits].parse::<f64>().ok()?);
+		end += 1 + digits;
+	}
+	let offset = match bytes.get(end) {
+		Some(b'Z') | Some(b'z') if bytes.len() == end + 1 => 0,
+		Some(sign @ b'+') | Some(sign @ b'-') if bytes.len() == end + 6 && bytes[end + 3] == b':' => {
+			let offset = number(end + 1..end + 3)? * 3600 + number(end + 4..end + 6)? * 60;
+			if *sign == b'-' {
+				-offset
+			} else {
+				offset
+			}
+		}
+		_ => return None,
+	};
+	// Days from the civil date, see http://howardhinnant.github.io/date_algorithms.html
+	let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+	let era = y.div_euclid(400);
+	let year_of_era = y - era * 400;
+	let day_of_year = (153 * m + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146_097 + day_of_era - 719_468;
+	Some((days * 86400 + hour * 3600 + minute * 60 + second - offset, fraction))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+
+	fn edn(text: &str) -> Vec<u8> {
+		parse(text).unwrap()
+	}
+
+	fn position(text: &str) -> (usize, usize) {
+		match parse(text) {
+			Err(Error::Parse(line, column, _)) => (line, column),
+			result => panic!("{:?}", result),
+		}
+	}
+
+	#[test]
+	fn items() {
+		assert_eq!(edn("[1, -2, \"a\", 'b', h'ff', {1: []}]"), [
+			0x86, 0x01, 0x21, 0x61, b'a', 0x41, b'b', 0x41, 0xFF, 0xA1, 0x01, 0x80
+		]);
+		assert_eq!(edn("1(2) / a tag / # a comment\n"), [0xC1, 0x02]);
+		assert_eq!(edn("[false, true, null, undefined, simple(16), simple(255)]"), [
+			0x86, 0xF4, 0xF5, 0xF6, 0xF7, 0xF0, 0xF8, 0xFF
+		]);
+		assert_eq!(edn("[0x10, 0o10, 0b10, 1.5, -Infinity, NaN]"), [
+			0x86, 0x10, 0x08, 0x02, 0xF9, 0x3E, 0x00, 0xF9, 0xFC, 0x00, 0xF9, 0x7E, 0x00
+		]);
+		assert_eq!(edn("-18446744073709551616"), [0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+		assert_eq!(edn("0.1"), [0xFB, 0x3F, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]);
+		assert!(matches!(parse("simple(24)"), Err(Error::Parse(1, 8, _))));
+		assert!(matches!(parse("18446744073709551616"), Err(Error::Parse(1, 1, _))));
+	}
+
+	#[test]
+	fn indicators() {
+		assert_eq!(edn("1_0"), [0x18, 0x01]);
+		assert_eq!(edn("1_1"), [0x19, 0x00, 0x01]);
+		assert_eq!(edn("1_2"), [0x1A, 0x00, 0x00, 0x00, 0x01]);
+		assert_eq!(edn("1_3"), [0x1B, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+		assert_eq!(edn("1_i"), [0x01]);
+		assert_eq!(edn("\"a\"_0"), [0x78, 0x01, b'a']);
+		assert_eq!(edn("[_1 1]"), [0x99, 0x00, 0x01, 0x01]);
+		assert_eq!(edn("{_0}"), [0xB8, 0x00]);
+		assert_eq!(edn("2_0(3)"), [0xD8, 0x02, 0x03]);
+		assert_eq!(edn("1.5_2"), [0xFA, 0x3F, 0xC0, 0x00, 0x00]);
+		assert_eq!(edn("NaN_3"), [0xFB, 0x7F, 0xF8, 0, 0, 0, 0, 0, 0]);
+		assert!(matches!(parse("256_0"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("24_i"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("0.1_1"), Err(Error::Parse(1, 1, _))));
+	}
+
+	#[test]
+	fn indefinite() {
+		assert_eq!(edn("[_ 1, [_ ]]"), [0x9F, 0x01, 0x9F, 0xFF, 0xFF]);
+		assert_eq!(edn("_ {1: 2}"), [0xBF, 0x01, 0x02, 0xFF]);
+		assert_eq!(edn("(_ h'01', h'')"), [0x5F, 0x41, 0x01, 0x40, 0xFF]);
+		assert_eq!(edn("(_ \"a\")"), [0x7F, 0x61, b'a', 0xFF]);
+		assert_eq!(edn("''_"), [0x5F, 0xFF]);
+		assert_eq!(edn("\"\"_"), [0x7F, 0xFF]);
+		assert!(matches!(parse("(_ h'01', \"a\")"), Err(Error::Parse(1, 11, _))));
+		assert!(matches!(parse("(_ )"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("'a'_"), Err(Error::Parse(1, 1, _))));
+	}
+
+	#[test]
+	fn escapes() {
+		assert_eq!(edn("\"\\\"\\\\\\/\\n\\u00e9\""), [0x66, b'"', b'\\', b'/', b'\n', 0xC3, 0xA9]);
+		assert_eq!(edn("\"\\ud83d\\ude00\""), [0x64, 0xF0, 0x9F, 0x98, 0x80]);
+		assert_eq!(edn("'\\''"), [0x41, b'\'']);
+		// A high surrogate must be followed by a low one.
+		assert!(matches!(parse("\"\\ud83d\\u0041\""), Err(Error::Parse(1, 8, _))));
+		assert!(matches!(parse("\"\\ud83d\\ud83d\""), Err(Error::Parse(1, 8, _))));
+		assert!(matches!(parse("\"\\ud83d\""), Err(Error::Parse(1, 2, _))));
+		assert!(matches!(parse("\"\\ude00\""), Err(Error::Parse(1, 2, _))));
+		assert!(matches!(parse("\"\\u+0e9\""), Err(Error::Parse(1, 2, _))));
+		assert!(matches!(parse("\"\\x\""), Err(Error::Parse(1, 2, _))));
+	}
+
+	#[test]
+	fn embedded() {
+		assert_eq!(edn("<<1, \"a\">>"), [0x43, 0x01, 0x61, b'a']);
+		assert_eq!(edn("<< >>"), [0x40]);
+		assert_eq!(edn("<<<<1>>>>_0"), [0x58, 0x02, 0x41, 0x01]);
+		assert!(matches!(parse("<<1 2>>"), Err(Error::Parse(1, 5, _))));
+	}
+
+	#[test]
+	fn app_strings() {
+		assert_eq!(edn("h'01 ab # comment\n / comment / FF'"), [0x43, 0x01, 0xAB, 0xFF]);
+		assert_eq!(edn("b64'AQID+w=='"), [0x44, 0x01, 0x02, 0x03, 0xFB]);
+		assert_eq!(edn("b64'AQID-w'"), [0x44, 0x01, 0x02, 0x03, 0xFB]);
+		assert_eq!(edn("b64''"), [0x40]);
+		assert!(matches!(parse("b64'AQJ'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("b64'A'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("b64'A=Q'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("h'abc'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("x'00'"), Err(Error::Parse(1, 1, _))));
+	}
+
+	#[test]
+	fn date_times() {
+		assert_eq!(edn("dt'1970-01-01T00:00:00Z'"), [0x00]);
+		assert_eq!(edn("DT'2013-03-21T20:04:00Z'"), [0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0]);
+		assert_eq!(edn("DT'2013-03-21T22:04:00+02:00'"), [0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0]);
+		assert_eq!(edn("dt'1969-12-31T23:59:59Z'"), [0x20]);
+		assert_eq!(edn("dt'1970-01-01T00:00:01.5Z'"), [0xF9, 0x3E, 0x00]);
+		assert_eq!(edn("dt'1970-01-01T00:00:01Z'_1"), [0x19, 0x00, 0x01]);
+		assert!(matches!(parse("dt'1970-13-01T00:00:00Z'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("dt'1970-01-01 00:00:00Z'"), Err(Error::Parse(1, 1, _))));
+		assert!(matches!(parse("dt'1970-01-01T00:00:00'"), Err(Error::Parse(1, 1, _))));
+	}
+
+	#[test]
+	fn positions() {
+		assert_eq!(position("[1,\n  2,\n  ?]"), (3, 3));
+		assert_eq!(position("\"é\" x"), (1, 5));
+		assert_eq!(position("[1 2]"), (1, 4));
+		assert_eq!(position("{1 2}"), (1, 4));
+		assert_eq!(position("1(2"), (1, 4));
+		assert_eq!(position("\n\n  \"abc"), (3, 3));
+		assert_eq!(position("1 / open"), (1, 3));
+		assert_eq!(position("1 2"), (1, 3));
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let mut text = "[".repeat(DEPTH_LIMIT);
+		text.push('1');
+		text.push_str(&"]".repeat(DEPTH_LIMIT));
+		let mut expected = vec![0x81; DEPTH_LIMIT];
+		expected.push(0x01);
+		assert_eq!(edn(&text), expected);
+		assert!(matches!(parse(&alloc::format!("1({})", text)), Err(Error::Limit(LimitKind::Depth))));
+		assert!(matches!(parse(&"<<".repeat(100_000)), Err(Error::Limit(LimitKind::Depth))));
+	}
+}
@@ -0,0 +1,117 @@
+use crate::error::*;
+use crate::scan::{Scan, Scanner};
+use crate::Result;
+use embedded_io_async::{Read, Write};
+
+/// Read exactly one data item from `reader` into `buffer`
+/// and return the part of `buffer` it fills.
+///
+/// The item is scanned while it is read, so nothing is read past it.
+/// An item bigger than `buffer` is rejected with
+/// `Error::Limit(LimitKind::ReadBuffer)`.
+pub async fn read_item<'b, R>(reader: &mut R, buffer: &'b mut [u8]) -> Result<&'b [u8]>
+where
+	R: Read,
+{
+	let mut scanner = Scanner::new();
+	let mut filled = 0;
+	loop {
+		match scanner.scan(&buffer[..filled])? {
+			Scan::Complete(len) => return Ok(&buffer[..len]),
+			Scan::NeedMore(more) => {
+				let end = match filled.checked_add(more) {
+					Some(end) if end <= buffer.len() => end,
+					_ => return Err(Error::Limit(LimitKind::ReadBuffer)),
+				};
+				reader.read_exact(&mut buffer[filled..end]).await?;
+				filled = end;
+			}
+		}
+	}
+}
+
+/// Write `bytes` to `writer` and return the number of bytes written.
+pub async fn write_item<W>(writer: &mut W, bytes: &[u8]) -> Result<usize>
+where
+	W: Write,
+{
+	writer.write_all(bytes).await.map_err(Error::embedded_io)?;
+	Ok(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::future::Future;
+	use core::pin::pin;
+	use core::task::{Context, Poll, Waker};
+
+	/// [1, {"a": h'02'}] then "b"
+	const ITEMS: [u8; 9] = [0x82, 0x01, 0xA1, 0x61, 0x61, 0x41, 0x02, 0x61, 0x62];
+
+	/// Poll `future` until it completes, the readers used here never wait.
+	fn block_on<F: Future>(future: F) -> F::Output {
+		let mut future = pin!(future);
+		let mut context = Context::from_waker(Waker::noop());
+		loop {
+			if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+				return output;
+			}
+		}
+	}
+
+	/// A stream handing out one byte per read.
+	struct Trickle<'a>(&'a [u8]);
+
+	impl embedded_io_async::ErrorType for Trickle<'_> {
+		type Error = core::convert::Infallible;
+	}
+
+	impl Read for Trickle<'_> {
+		async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+			match (self.0.split_first(), buf.first_mut()) {
+				(Some((byte, rest)), Some(first)) => {
+					*first = *byte;
+					self.0 = rest;
+					Ok(1)
+				}
+				_ => Ok(0),
+			}
+		}
+	}
+
+	#[test]
+	fn items_one_byte_at_a_time() {
+		let mut reader = Trickle(&ITEMS);
+		let mut buffer = [0u8; 16];
+		assert_eq!(block_on(read_item(&mut reader, &mut buffer)).unwrap(), &ITEMS[..7]);
+		assert_eq!(block_on(read_item(&mut reader, &mut buffer)).unwrap(), &ITEMS[7..]);
+		assert!(matches!(block_on(read_item(&mut reader, &mut buffer)), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn items_bigger_than_the_buffer() {
+		let mut buffer = [0u8; 6];
+		let result = block_on(read_item(&mut &ITEMS[..], &mut buffer));
+		assert!(matches!(result, Err(Error::Limit(LimitKind::ReadBuffer))));
+	}
+
+	#[test]
+	fn truncated_items() {
+		let mut buffer = [0u8; 16];
+		assert!(matches!(block_on(read_item(&mut &ITEMS[..5], &mut buffer)), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut output = [0u8; 16];
+		let mut buffer = [0u8; 16];
+		let len = block_on(crate::to_embedded_async(&mut output[..], &mut buffer, &(1u8, "b"))).unwrap();
+		assert_eq!(&output[..len], [0x82, 0x01, 0x61, 0x62]);
+		let value: (u8, &str) = block_on(crate::from_embedded_async(&output[..len], &mut buffer)).unwrap();
+		assert_eq!(value, (1, "b"));
+
+		let mut short = [0u8; 2];
+		assert!(block_on(write_item(&mut &mut short[..], &ITEMS)).is_err());
+	}
+}
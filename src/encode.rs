@@ -0,0 +1,451 @@
+use crate::cbor::*;
+use crate::error::*;
+use crate::write::*;
+use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use half::f16;
+
+/// The additional information of the shortest encoding of `argument`.
+#[inline]
+pub(crate) fn shortest_info(argument: u64) -> u8 {
+	if argument < SIZE_8 as u64 {
+		argument as u8
+	} else if argument <= u8::MAX as u64 {
+		SIZE_8
+	} else if argument <= u16::MAX as u64 {
+		SIZE_16
+	} else if argument <= u32::MAX as u64 {
+		SIZE_32
+	} else {
+		SIZE_64
+	}
+}
+
+/// Whether `argument` can be encoded with the additional information `info`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn fits(argument: u64, info: u8) -> bool {
+	match info {
+		SIZE_8 => argument <= u8::MAX as u64,
+		SIZE_16 => argument <= u16::MAX as u64,
+		SIZE_32 => argument <= u32::MAX as u64,
+		SIZE_64 => true,
+		info => info < SIZE_8 && argument == info as u64,
+	}
+}
+
+/// Encode in `buffer` a header of type `major` with its `argument` on
+/// the additional information `info`, and return its length.
+/// The argument is truncated if it does not fit.
+#[inline]
+pub(crate) fn encode_header(buffer: &mut [u8; 9], major: u8, argument: u64, info: u8) -> usize {
+	buffer[0] = major << 5 | info;
+	match info {
+		SIZE_8 => {
+			buffer[1] = argument as u8;
+			2
+		}
+		SIZE_16 => {
+			BigEndian::write_u16(&mut buffer[1..], argument as u16);
+			3
+		}
+		SIZE_32 => {
+			BigEndian::write_u32(&mut buffer[1..], argument as u32);
+			5
+		}
+		SIZE_64 => {
+			BigEndian::write_u64(&mut buffer[1..], argument);
+			9
+		}
+		_ => 1,
+	}
+}
+
+/// The additional information of the shortest float encoding `value` exactly.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn shortest_float_info(value: f64) -> u8 {
+	if float_fits(value, SIZE_16) {
+		SIZE_16
+	} else if float_fits(value, SIZE_32) {
+		SIZE_32
+	} else {
+		SIZE_64
+	}
+}
+
+/// Whether `value` is encoded exactly as a float of additional information `info`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn float_fits(value: f64, info: u8) -> bool {
+	match info {
+		SIZE_16 => value.is_nan() || f16::from_f64(value).to_f64() == value,
+		SIZE_32 => value.is_nan() || (value as f32) as f64 == value,
+		SIZE_64 => true,
+		_ => false,
+	}
+}
+
+/// Encode in `buffer` the float `value` with the additional information
+/// `info`, `SIZE_16` to `SIZE_64`, and return its length. The value is
+/// rounded if it does not fit, a NaN on 16 bits is the quiet NaN.
+#[inline]
+pub(crate) fn encode_float(buffer: &mut [u8; 9], value: f64, info: u8) -> usize {
+	buffer[0] = MAJOR_PRIMITIVE << 5 | info;
+	match info {
+		SIZE_16 => {
+			let bits = if value.is_nan() { 0x7E00 } else { f16::from_f64(value).to_bits() };
+			BigEndian::write_u16(&mut buffer[1..], bits);
+			3
+		}
+		SIZE_32 => {
+			BigEndian::write_f32(&mut buffer[1..], value as f32);
+			5
+		}
+		_ => {
+			buffer[0] = HEADER_FLOAT_64;
+			BigEndian::write_f64(&mut buffer[1..], value);
+			9
+		}
+	}
+}
+
+/// A container or a tag opened on a checked `Encoder`.
+#[cfg(feature = "alloc")]
+struct Frame {
+	initial: u8,
+	remaining: Option<u64>,
+}
+
+/// A push encoder writing headers and data items to a `Writer`.
+///
+/// Every method returns the number of bytes written. The encoder does not
+/// verify that containers receive the announced number of items unless it
+/// is created with `Encoder::with_checker`.
+pub struct Encoder<W> {
+	writer: W,
+	buffer: [u8; 9],
+	#[cfg(feature = "alloc")]
+	checker: Option<Vec<Frame>>,
+}
+
+impl<W: Writer> Encoder<W> {
+	pub fn new(writer: W) -> Self {
+		Encoder {
+			writer,
+			buffer: [0u8; 9],
+			#[cfg(feature = "alloc")]
+			checker: None,
+		}
+	}
+
+	/// Create an encoder that keeps track of the open containers and
+	/// returns an error when their lengths are not respected.
+	#[cfg(feature = "alloc")]
+	pub fn with_checker(writer: W) -> Self {
+		Encoder {
+			writer,
+			buffer: [0u8; 9],
+			checker: Some(Vec::new()),
+		}
+	}
+
+	pub fn writer(&self) -> &W {
+		&self.writer
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+
+	/// Check that every opened container has been completed.
+	/// Always succeed when the checker is disabled.
+	#[cfg(feature = "alloc")]
+	pub fn end(&self) -> Result<()> {
+		match self.checker.as_ref().and_then(|frames| frames.last()) {
+			Some(frame) => Err(Error::Unexpected(frame.initial, "end of container")),
+			None => Ok(()),
+		}
+	}
+
+	/// Check that every opened container has been completed.
+	/// Always succeed as there is no checker without the `alloc` feature.
+	#[cfg(not(feature = "alloc"))]
+	pub fn end(&self) -> Result<()> {
+		Ok(())
+	}
+
+	/// Write an unsigned integer.
+	#[inline]
+	pub fn push_uint(&mut self, value: u64) -> Result<usize> {
+		self.check_item(HEADER_POSITIVE_START)?;
+		self.write_header(MAJOR_POSITIVE, value)
+	}
+
+	/// Write the negative integer `-1 - value`.
+	#[inline]
+	pub fn push_negint(&mut self, value: u64) -> Result<usize> {
+		self.check_item(HEADER_NEGATIVE_START)?;
+		self.write_header(MAJOR_NEGATIVE, value)
+	}
+
+	/// Write a definite byte string.
+	#[inline]
+	pub fn push_bytes(&mut self, value: &[u8]) -> Result<usize> {
+		self.check_item(HEADER_BYTE_START)?;
+		let len = self.write_header(MAJOR_BYTE, value.len() as u64)?;
+		Ok(len + self.writer.write(value)?)
+	}
+
+	/// Write a definite text string.
+	#[inline]
+	pub fn push_text(&mut self, value: &str) -> Result<usize> {
+		self.check_item(HEADER_TEXT_START)?;
+		let len = self.write_header(MAJOR_TEXT, value.len() as u64)?;
+		Ok(len + self.writer.write(value.as_bytes())?)
+	}
+
+	/// Start an array of `len` elements, or an indefinite one
+	/// to be closed by `push_break`.
+	#[inline]
+	pub fn begin_array(&mut self, len: Option<u64>) -> Result<usize> {
+		self.begin(MAJOR_ARRAY, len, len)
+	}
+
+	/// Start a map of `len` pairs, or an indefinite one
+	/// to be closed by `push_break`.
+	#[inline]
+	pub fn begin_map(&mut self, len: Option<u64>) -> Result<usize> {
+		let items = match len {
+			Some(len) => Some(len.checked_mul(2).ok_or(Error::Limit(LimitKind::Index))?),
+			None => None,
+		};
+		self.begin(MAJOR_MAP, len, items)
+	}
+
+	/// Write a tag applying to the next data item.
+	/// The checker expects exactly one item after it.
+	#[inline]
+	pub fn push_tag(&mut self, tag: u64) -> Result<usize> {
+		self.begin(MAJOR_TAG, Some(tag), Some(1))
+	}
+
+	/// Write a simple value. Values from 24 to 31 are reserved.
+	#[inline]
+	pub fn push_simple(&mut self, value: u8) -> Result<usize> {
+		if (SIZE_8..32).contains(&value) {
+			return Err(Error::Unassigned(HEADER_SIMPLE_8));
+		}
+		self.check_item(MAJOR_PRIMITIVE << 5)?;
+		self.write_header(MAJOR_PRIMITIVE, value as u64)
+	}
+
+	/// Write a single precision float.
+	#[inline]
+	pub fn push_f32(&mut self, value: f32) -> Result<usize> {
+		self.check_item(HEADER_FLOAT_32)?;
+		let len = encode_float(&mut self.buffer, value as f64, SIZE_32);
+		self.writer.write(&self.buffer[..len])
+	}
+
+	/// Write a double precision float.
+	#[inline]
+	pub fn push_float(&mut self, value: f64) -> Result<usize> {
+		self.check_item(HEADER_FLOAT_64)?;
+		let len = encode_float(&mut self.buffer, value, SIZE_64);
+		self.writer.write(&self.buffer[..len])
+	}
+
+	/// Close the innermost indefinite container.
+	#[inline]
+	pub fn push_break(&mut self) -> Result<usize> {
+		#[cfg(feature = "alloc")]
+		if let Some(frames) = self.checker.as_mut() {
+			match frames.last() {
+				Some(Frame { remaining: None, .. }) => {
+					frames.pop();
+				}
+				_ => return Err(Error::Unexpected(HEADER_BREAK, "indefinite container")),
+			}
+		}
+		self.buffer[0] = HEADER_BREAK;
+		self.writer.write(&self.buffer[..1])
+	}
+
+	/// Write the header of a definite text string of `len` bytes,
+	/// its content being written with `write_payload`.
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	pub(crate) fn begin_text(&mut self, len: u64) -> Result<usize> {
+		self.check_item(HEADER_TEXT_START)?;
+		self.write_header(MAJOR_TEXT, len)
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	pub(crate) fn write_payload(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.writer.write(bytes)
+	}
+
+	/// Write an already encoded data item verbatim.
+	#[inline]
+	pub fn push_raw(&mut self, bytes: &[u8]) -> Result<usize> {
+		if let Some(initial) = bytes.first() {
+			self.check_item(*initial)?;
+		}
+		self.writer.write(bytes)
+	}
+
+	#[inline]
+	fn begin(&mut self, major: u8, len: Option<u64>, items: Option<u64>) -> Result<usize> {
+		let initial = major << 5;
+		self.check_item(initial)?;
+		#[cfg(not(feature = "alloc"))]
+		let _ = items;
+		#[cfg(feature = "alloc")]
+		if let Some(frames) = self.checker.as_mut() {
+			if items != Some(0) {
+				frames.push(Frame {
+					initial,
+					remaining: items,
+				});
+			}
+		}
+		match len {
+			Some(len) => self.write_header(major, len),
+			None => {
+				self.buffer[0] = initial | SIZE_INFINITE;
+				self.writer.write(&self.buffer[..1])
+			}
+		}
+	}
+
+	/// Count one item in the innermost container and
+	/// close every container that is now complete.
+	#[cfg(feature = "alloc")]
+	#[inline]
+	fn check_item(&mut self, initial: u8) -> Result<()> {
+		if let Some(frames) = self.checker.as_mut() {
+			if let Some(Frame {
+				remaining: Some(remaining),
+				..
+			}) = frames.last_mut()
+			{
+				match remaining.checked_sub(1) {
+					Some(value) => *remaining = value,
+					None => return Err(Error::Unexpected(initial, "end of container")),
+				}
+			}
+			while let Some(Frame {
+				remaining: Some(0),
+				..
+			}) = frames.last()
+			{
+				frames.pop();
+			}
+		}
+		Ok(())
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	fn check_item(&mut self, _initial: u8) -> Result<()> {
+		Ok(())
+	}
+
+	#[inline]
+	fn write_header(&mut self, major: u8, value: u64) -> Result<usize> {
+		let len = encode_header(&mut self.buffer, major, value, shortest_info(value));
+		self.writer.write(&self.buffer[..len])
+	}
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+
+	fn checked(buffer: &mut Vec<u8>) -> Encoder<VecWriter<'_>> {
+		Encoder::with_checker(VecWriter::new(buffer))
+	}
+
+	#[test]
+	fn complete_items_pass() {
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_array(Some(2)).unwrap();
+		encoder.push_tag(1).unwrap();
+		encoder.push_tag(2).unwrap();
+		encoder.push_uint(3).unwrap();
+		encoder.begin_map(None).unwrap();
+		encoder.push_text("a").unwrap();
+		encoder.begin_array(Some(0)).unwrap();
+		encoder.push_break().unwrap();
+		encoder.end().unwrap();
+		assert_eq!(buffer, [0x82, 0xC1, 0xC2, 0x03, 0xBF, 0x61, 0x61, 0x80, 0xFF]);
+	}
+
+	#[test]
+	fn unfinished_containers_fail_at_end() {
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_array(Some(2)).unwrap();
+		encoder.push_uint(1).unwrap();
+		assert!(matches!(encoder.end(), Err(Error::Unexpected(0x80, _))));
+
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_map(None).unwrap();
+		assert!(matches!(encoder.end(), Err(Error::Unexpected(0xA0, _))));
+	}
+
+	#[test]
+	fn extra_items_fail() {
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_map(Some(1)).unwrap();
+		encoder.push_uint(1).unwrap();
+		encoder.push_uint(2).unwrap();
+		// The map is complete, a top level item may follow.
+		encoder.push_uint(3).unwrap();
+
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_array(Some(1)).unwrap();
+		assert!(matches!(encoder.push_break(), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(checked(&mut Vec::new()).push_break(), Err(Error::Unexpected(HEADER_BREAK, _))));
+	}
+
+	#[test]
+	fn dangling_tags_fail() {
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.push_tag(1).unwrap();
+		assert!(matches!(encoder.end(), Err(Error::Unexpected(0xC0, _))));
+
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_array(None).unwrap();
+		encoder.push_tag(1).unwrap();
+		assert!(matches!(encoder.push_break(), Err(Error::Unexpected(HEADER_BREAK, _))));
+
+		let mut buffer = Vec::new();
+		let mut encoder = checked(&mut buffer);
+		encoder.begin_array(Some(1)).unwrap();
+		encoder.push_tag(1).unwrap();
+		encoder.push_tag(2).unwrap();
+		assert!(encoder.end().is_err());
+		encoder.push_simple(SIMPLE_NULL).unwrap();
+		encoder.end().unwrap();
+	}
+
+	#[test]
+	fn unchecked_encoder_accepts_anything() {
+		let mut buffer = Vec::new();
+		let mut encoder = Encoder::new(VecWriter::new(&mut buffer));
+		encoder.push_tag(1).unwrap();
+		encoder.push_break().unwrap();
+		encoder.end().unwrap();
+	}
+}
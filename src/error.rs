@@ -1,3 +1,9 @@
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+use core::fmt;
+
 /// An enum error that represent all possible errors comming from
 /// this codec.
 
@@ -6,14 +12,14 @@ pub enum Error {
 	/// This error is generated when a serde error is encounter
 	/// it describe when the error occured (SerdeWhen::{Serialization, Deserialization})
 	/// and what the error is in textual form.
-	Serde(SerdeWhen, String),
+	Serde(SerdeWhen, Message),
 
 	/// An error which is not related to this codec.
 	/// Can be one of :
 	/// - std::io::Error
 	/// - std::str::Utf8Error
 	/// - std::num::TryFromIntError
-	Other(OtherKind, Box<dyn std::error::Error>),
+	Other(OtherKind, Source),
 
 	/// Represent a header that is unsupported to deserialize.
 	/// - Any Infinite things (BYTE, STRING, MAP, ARRAY)
@@ -26,8 +32,18 @@ pub enum Error {
 	/// Unexpected header form deserializer
 	Unexpected(u8, &'static str),
 
-	/// An error that is not he not defined by an enum because too singular
-	Message(&'static str),
+	/// The input ended before the current data item was complete.
+	Eof,
+
+	/// A buffer limit has been reached.
+	Limit(LimitKind),
+
+	/// Some bytes remain after the data item, at the given offset.
+	TrailingData(usize),
+
+	/// Invalid diagnostic notation at the given line and column,
+	/// both starting at 1, with what was expected there.
+	Parse(usize, usize, &'static str),
 }
 
 /// Define when a serde error occured
@@ -39,6 +55,128 @@ pub enum SerdeWhen {
 	Deserialization,
 }
 
+/// Capacity in bytes of a `Message` without the `alloc` feature.
+pub const MESSAGE_CAPACITY: usize = 64;
+
+/// A serde error message.
+///
+/// Without the `alloc` feature, it is stored inline and truncated
+/// to `MESSAGE_CAPACITY` bytes.
+#[derive(Clone)]
+pub struct Message {
+	#[cfg(feature = "alloc")]
+	text: String,
+	#[cfg(not(feature = "alloc"))]
+	bytes: [u8; MESSAGE_CAPACITY],
+	#[cfg(not(feature = "alloc"))]
+	len: usize,
+}
+
+impl Message {
+	#[cfg(feature = "alloc")]
+	fn new<T: fmt::Display>(msg: T) -> Self {
+		Message {
+			text: msg.to_string(),
+		}
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	fn new<T: fmt::Display>(msg: T) -> Self {
+		let mut message = Message {
+			bytes: [0u8; MESSAGE_CAPACITY],
+			len: 0,
+		};
+		// A message too long is not an error, it is only truncated.
+		let _ = fmt::write(&mut message, format_args!("{}", msg));
+		message
+	}
+
+	#[cfg(feature = "alloc")]
+	pub fn as_str(&self) -> &str {
+		&self.text
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	pub fn as_str(&self) -> &str {
+		// Only whole characters are written, see `write_str`.
+		core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+	}
+}
+
+#[cfg(not(feature = "alloc"))]
+impl fmt::Write for Message {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let mut len = s.len().min(MESSAGE_CAPACITY - self.len);
+		while !s.is_char_boundary(len) {
+			len -= 1;
+		}
+		self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+		self.len += len;
+		if len < s.len() {
+			Err(fmt::Error)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl fmt::Display for Message {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl fmt::Debug for Message {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(self.as_str(), f)
+	}
+}
+
+/// The error behind an `Error::Other`.
+///
+/// It is only kept with the `std` feature, where it is also
+/// the `source` of the `Error`.
+pub struct Source {
+	#[cfg(feature = "std")]
+	error: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl Source {
+	#[cfg(feature = "std")]
+	fn new<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self {
+		Source {
+			error: Box::new(error),
+		}
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn new<E>(_: E) -> Self {
+		Source {}
+	}
+
+	#[cfg(feature = "std")]
+	pub fn get(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+		&*self.error
+	}
+
+	#[cfg(feature = "std")]
+	pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+		self.error
+	}
+}
+
+impl fmt::Debug for Source {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&self.error, f)
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Source")
+	}
+}
+
 /// Define the other kind of error.
 #[derive(Debug)]
 pub enum OtherKind {
@@ -50,8 +188,73 @@ pub enum OtherKind {
 	Numerical,
 }
 
-impl std::fmt::Display for Error {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Define which limit has been reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+	/// An element is bigger than the buffer of a limited reader.
+	ReadBuffer,
+	/// The output slice is too small to hold the encoded data.
+	WriteSlice,
+	/// An index or a length exceed usize::max.
+	Index,
+	/// A frame is bigger than the maximum size of a codec.
+	Frame,
+	/// Containers are nested deeper than a fixed-size stack allows.
+	Depth,
+}
+
+/// A coarse classification of an `Error`, meant to be matched
+/// by callers instead of the error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+	/// Reading from or writing to the underlying io failed.
+	Io,
+	/// The input ended in the middle of a data item.
+	Eof,
+	/// The input is not well-formed CBOR.
+	Syntax,
+	/// The input is well-formed CBOR but does not match the expected data.
+	Data,
+	/// A buffer limit has been reached.
+	Limit,
+}
+
+impl Error {
+	/// Classify this error into an `ErrorKind`.
+	pub fn classify(&self) -> ErrorKind {
+		match self {
+			Error::Serde(_, _) => ErrorKind::Data,
+			Error::Other(OtherKind::Io, _) => ErrorKind::Io,
+			Error::Other(OtherKind::Utf8, _) => ErrorKind::Syntax,
+			Error::Other(OtherKind::Numerical, _) => ErrorKind::Data,
+			Error::Unsupported(_) => ErrorKind::Data,
+			Error::Unassigned(_) => ErrorKind::Syntax,
+			Error::Unexpected(_, _) => ErrorKind::Data,
+			Error::Eof => ErrorKind::Eof,
+			Error::Limit(_) => ErrorKind::Limit,
+			Error::TrailingData(_) => ErrorKind::Syntax,
+			Error::Parse(_, _, _) => ErrorKind::Syntax,
+		}
+	}
+}
+
+#[cfg(feature = "embedded-io")]
+impl Error {
+	/// Convert the error of an `embedded_io` stream.
+	#[cfg(feature = "std")]
+	pub(crate) fn embedded_io<E: embedded_io::Error>(error: E) -> Self {
+		Error::from(std::io::Error::from(std::io::ErrorKind::from(error.kind())))
+	}
+
+	/// Convert the error of an `embedded_io` stream.
+	#[cfg(not(feature = "std"))]
+	pub(crate) fn embedded_io<E: embedded_io::Error>(error: E) -> Self {
+		Error::Other(OtherKind::Io, Source::new(error))
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			Error::Serde(when, string) => {
 				let when = match when {
@@ -64,52 +267,176 @@ impl std::fmt::Display for Error {
 					when, string
 				)
 			}
-			Error::Other(kind, error) => {
-				let kind = match kind {
-					OtherKind::Io => "std::io::Error",
-					OtherKind::Utf8 => "std::str::Utf8Error",
-					OtherKind::Numerical => "std::num::TryFromIntError",
-				};
-				write!(f, "An error of type {} occured.\n{}", kind, error)
-			}
+			#[cfg(feature = "std")]
+			Error::Other(kind, source) => write!(f, "An error of type {} occured.\n{}", kind, source.error),
+			#[cfg(not(feature = "std"))]
+			Error::Other(kind, _) => write!(f, "An error of type {} occured.", kind),
 			Error::Unsupported(header) => write!(f, "Unsupported header {:#02x}", header),
 			Error::Unassigned(header) => write!(f, "Unassigned header {:#02x}", header),
 			Error::Unexpected(header, string) => {
 				write!(f, "Unexpected header: {:#02x} expected: {}", header, string)
 			}
-			Error::Message(string) => write!(f, "{}", string),
+			Error::Eof => write!(f, "Unexpected end of input."),
+			Error::Limit(LimitKind::ReadBuffer) => {
+				write!(f, "Buffer limit reached when reading an element.")
+			}
+			Error::Limit(LimitKind::WriteSlice) => {
+				write!(f, "Try to write after the end of the slice.")
+			}
+			Error::Limit(LimitKind::Index) => write!(f, "Index exceed usize::max."),
+			Error::Limit(LimitKind::Frame) => write!(f, "Frame exceed the maximum frame size."),
+			Error::Limit(LimitKind::Depth) => write!(f, "Containers nested too deeply."),
+			Error::TrailingData(offset) => write!(f, "Trailing data at offset {}", offset),
+			Error::Parse(line, column, expected) => {
+				write!(f, "Invalid diagnostic notation at line {}, column {}, expected: {}", line, column, expected)
+			}
 		}
 	}
 }
 
-impl std::error::Error for Error {}
+impl fmt::Display for OtherKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			OtherKind::Io => "std::io::Error",
+			OtherKind::Utf8 => "core::str::Utf8Error",
+			OtherKind::Numerical => "core::num::TryFromIntError",
+		})
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::Other(_, source) => Some(&*source.error),
+			_ => None,
+		}
+	}
+}
 
 impl serde::de::Error for Error {
-	fn custom<T: std::fmt::Display>(msg: T) -> Self {
-		Error::Serde(SerdeWhen::Deserialization, msg.to_string())
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Error::Serde(SerdeWhen::Deserialization, Message::new(msg))
 	}
 }
 
 impl serde::ser::Error for Error {
-	fn custom<T: std::fmt::Display>(msg: T) -> Self {
-		Error::Serde(SerdeWhen::Deserialization, msg.to_string())
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Error::Serde(SerdeWhen::Serialization, Message::new(msg))
 	}
 }
 
-impl std::convert::From<std::io::Error> for Error {
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
 	fn from(item: std::io::Error) -> Self {
-		Error::Other(OtherKind::Io, Box::new(item))
+		if item.kind() == std::io::ErrorKind::UnexpectedEof {
+			Error::Eof
+		} else {
+			Error::Other(OtherKind::Io, Source::new(item))
+		}
 	}
 }
 
-impl std::convert::From<std::str::Utf8Error> for Error {
-	fn from(item: std::str::Utf8Error) -> Self {
-		Error::Other(OtherKind::Utf8, Box::new(item))
+#[cfg(feature = "embedded-io")]
+impl<E: embedded_io::Error> From<embedded_io::ReadExactError<E>> for Error {
+	fn from(item: embedded_io::ReadExactError<E>) -> Self {
+		match item {
+			embedded_io::ReadExactError::UnexpectedEof => Error::Eof,
+			embedded_io::ReadExactError::Other(error) => Error::embedded_io(error),
+		}
+	}
+}
+
+impl From<core::str::Utf8Error> for Error {
+	fn from(item: core::str::Utf8Error) -> Self {
+		Error::Other(OtherKind::Utf8, Source::new(item))
+	}
+}
+
+impl From<core::num::TryFromIntError> for Error {
+	fn from(item: core::num::TryFromIntError) -> Self {
+		Error::Other(OtherKind::Numerical, Source::new(item))
 	}
 }
 
-impl std::convert::From<std::num::TryFromIntError> for Error {
-	fn from(item: std::num::TryFromIntError) -> Self {
-		Error::Other(OtherKind::Numerical, Box::new(item))
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::convert::TryFrom;
+
+	fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+	#[test]
+	fn error_is_send_sync() {
+		assert_send_sync::<Error>();
+	}
+
+	#[test]
+	fn classify() {
+		let de = <Error as serde::de::Error>::custom("invalid");
+		let ser = <Error as serde::ser::Error>::custom("invalid");
+		assert_eq!(de.classify(), ErrorKind::Data);
+		assert_eq!(ser.classify(), ErrorKind::Data);
+		#[cfg(feature = "std")]
+		{
+			let io = Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+			assert_eq!(io.classify(), ErrorKind::Io);
+			let eof = Error::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+			assert_eq!(eof.classify(), ErrorKind::Eof);
+		}
+		let utf8 = Error::from(core::str::from_utf8(core::hint::black_box(&[0xFF])).unwrap_err());
+		assert_eq!(utf8.classify(), ErrorKind::Syntax);
+		let numerical = Error::from(u8::try_from(256u32).unwrap_err());
+		assert_eq!(numerical.classify(), ErrorKind::Data);
+		assert_eq!(Error::Unsupported(0xC0).classify(), ErrorKind::Data);
+		assert_eq!(Error::Unassigned(0x1C).classify(), ErrorKind::Syntax);
+		assert_eq!(Error::Unexpected(0x01, "array").classify(), ErrorKind::Data);
+		assert_eq!(Error::Eof.classify(), ErrorKind::Eof);
+		assert_eq!(Error::Limit(LimitKind::Depth).classify(), ErrorKind::Limit);
+		assert_eq!(Error::TrailingData(1).classify(), ErrorKind::Syntax);
+		assert_eq!(Error::Parse(1, 1, "value").classify(), ErrorKind::Syntax);
+	}
+
+	#[test]
+	fn other_keeps_its_arity() {
+		let error = Error::from(u8::try_from(256u32).unwrap_err());
+		assert!(matches!(error, Error::Other(OtherKind::Numerical, _)));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn other_has_a_source() {
+		use std::error::Error as _;
+
+		let error = Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+		let source = error.source().unwrap();
+		assert_eq!(
+			source.downcast_ref::<std::io::Error>().unwrap().kind(),
+			std::io::ErrorKind::BrokenPipe
+		);
+		match error {
+			Error::Other(OtherKind::Io, source) => {
+				assert!(source.get().is::<std::io::Error>());
+				assert!(source.into_inner().downcast::<std::io::Error>().is_ok());
+			}
+			_ => panic!("not an io error"),
+		}
+		assert!(Error::Eof.source().is_none());
+	}
+
+	#[test]
+	fn serde_messages() {
+		let long = [b'x'; MESSAGE_CAPACITY + 1];
+		let long = core::str::from_utf8(&long).unwrap();
+		match <Error as serde::de::Error>::custom(long) {
+			#[cfg(feature = "alloc")]
+			Error::Serde(SerdeWhen::Deserialization, message) => assert_eq!(message.as_str(), long),
+			#[cfg(not(feature = "alloc"))]
+			Error::Serde(SerdeWhen::Deserialization, message) => {
+				assert_eq!(message.as_str(), &long[..MESSAGE_CAPACITY])
+			}
+			error => panic!("{:?}", error),
+		}
 	}
 }
+
@@ -1,19 +1,68 @@
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 extern crate byteorder;
 extern crate half;
 extern crate serde;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "embedded-io")]
+extern crate embedded_io;
+#[cfg(feature = "embedded-io-async")]
+extern crate embedded_io_async;
+#[cfg(feature = "heapless")]
+extern crate heapless;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "codec")]
+extern crate tokio_util;
 
+#[cfg(feature = "alloc")]
+mod annotate;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "bytes")]
+pub mod buf;
 pub mod cbor;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod decode;
 pub mod deserialize;
+#[cfg(feature = "alloc")]
+mod diag;
+#[cfg(feature = "alloc")]
+mod edn;
+#[cfg(feature = "embedded-io-async")]
+pub mod embedded_async;
+pub mod encode;
 pub mod error;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod raw;
 pub mod read;
+pub mod scan;
 pub mod serialize;
+#[cfg(feature = "alloc")]
+pub mod value;
+pub mod view;
 pub mod write;
 
 pub type Result<T> = core::result::Result<T, error::Error>;
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use serde::de;
 use serde::ser;
 
+#[cfg(feature = "std")]
 pub fn to_writer<S, W>(output: W, value: &S) -> Result<usize>
 where
 	S: ser::Serialize,
@@ -24,15 +73,77 @@ where
 	)))
 }
 
+/// Serialize `value` to the file at `path`, creating or truncating it.
+#[cfg(feature = "std")]
+pub fn to_file<S, P>(path: P, value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+	P: AsRef<std::path::Path>,
+{
+	let mut output = std::io::BufWriter::new(std::fs::File::create(path)?);
+	let len = to_writer(&mut output, value)?;
+	std::io::Write::flush(&mut output)?;
+	Ok(len)
+}
+
+#[cfg(feature = "embedded-io")]
+pub fn to_embedded<S, W>(output: W, value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+	W: embedded_io::Write,
+{
+	value.serialize(&mut serialize::Serializer::new(write::EmbeddedWriter::new(
+		output,
+	)))
+}
+
+/// Serialize `value` into `buffer`, then write it to `output`.
+#[cfg(feature = "embedded-io-async")]
+pub async fn to_embedded_async<S, W>(mut output: W, buffer: &mut [u8], value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+	W: embedded_io_async::Write,
+{
+	let len = to_slice(buffer, value)?;
+	embedded_async::write_item(&mut output, &buffer[..len]).await
+}
+
+#[cfg(feature = "tokio")]
+pub async fn to_async_writer<S, W>(mut output: W, value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+	W: tokio::io::AsyncWrite + Unpin,
+{
+	async_io::write_item(&mut output, &to_vec(value)?).await
+}
+
+#[cfg(feature = "alloc")]
 pub fn to_vec<S>(value: &S) -> Result<Vec<u8>>
 where
 	S: ser::Serialize,
 {
-	let mut vec = vec![];
-	to_writer(&mut vec, value)?;
+	to_vec_with_capacity(value, 0)
+}
+
+#[cfg(feature = "alloc")]
+pub fn to_vec_with_capacity<S>(value: &S, capacity: usize) -> Result<Vec<u8>>
+where
+	S: ser::Serialize,
+{
+	let mut vec = Vec::with_capacity(capacity);
+	append_to_vec(&mut vec, value)?;
 	Ok(vec)
 }
 
+/// Serialize `value` at the end of `vec`, keeping its content and capacity.
+#[cfg(feature = "alloc")]
+pub fn append_to_vec<S>(vec: &mut Vec<u8>, value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+{
+	value.serialize(&mut serialize::Serializer::new(write::VecWriter::new(vec)))
+}
+
 pub fn to_slice<S>(slice: &mut [u8], value: &S) -> Result<usize>
 where
 	S: ser::Serialize,
@@ -42,6 +153,41 @@ where
 	)))
 }
 
+#[cfg(feature = "heapless")]
+pub fn to_heapless_vec<S, const N: usize>(value: &S) -> Result<heapless::Vec<u8, N>>
+where
+	S: ser::Serialize,
+{
+	let mut vec = heapless::Vec::new();
+	value.serialize(&mut serialize::Serializer::new(write::HeaplessWriter::new(
+		&mut vec,
+	)))?;
+	Ok(vec)
+}
+
+#[cfg(feature = "bytes")]
+pub fn to_bytes<S>(value: &S) -> Result<bytes::Bytes>
+where
+	S: ser::Serialize,
+{
+	let mut buf = bytes::BytesMut::new();
+	value.serialize(&mut serialize::Serializer::new(write::BufMutWriter::new(
+		&mut buf,
+	)))?;
+	Ok(buf.freeze())
+}
+
+/// The number of bytes `value` is serialized to, without writing them.
+pub fn serialized_size<S>(value: &S) -> Result<usize>
+where
+	S: ser::Serialize,
+{
+	let mut serializer = serialize::Serializer::new(write::SizeWriter::new());
+	value.serialize(&mut serializer)?;
+	Ok(serializer.into_inner().size())
+}
+
+#[cfg(feature = "std")]
 pub fn from_reader<T, R>(reader: R) -> Result<T>
 where
 	T: de::DeserializeOwned,
@@ -52,6 +198,7 @@ where
 	Ok(value)
 }
 
+#[cfg(feature = "std")]
 pub fn from_reader_limit<T, R>(reader: R, limit: usize) -> Result<T>
 where
 	T: de::DeserializeOwned,
@@ -62,11 +209,437 @@ where
 	Ok(value)
 }
 
+#[cfg(feature = "std")]
+pub fn from_bufread<T, R>(reader: R) -> Result<T>
+where
+	T: de::DeserializeOwned,
+	R: std::io::BufRead,
+{
+	let mut deserializer = deserialize::Deserializer::new(read::BufReadReader::new(reader));
+	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	Ok(value)
+}
+
+/// Deserialize the single data item of the file at `path`.
+#[cfg(feature = "std")]
+pub fn from_file<T, P>(path: P) -> Result<T>
+where
+	T: de::DeserializeOwned,
+	P: AsRef<std::path::Path>,
+{
+	let input = std::io::BufReader::new(std::fs::File::open(path)?);
+	let mut deserializer = deserialize::Deserializer::new(read::BufReadReader::new(input));
+	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	deserializer.end()?;
+	Ok(value)
+}
+
+/// Deserialize from `reader`, each item being copied to `scratch`.
+#[cfg(feature = "embedded-io")]
+pub fn from_embedded<T, R>(reader: R, scratch: &mut [u8]) -> Result<T>
+where
+	T: de::DeserializeOwned,
+	R: embedded_io::Read,
+{
+	let mut deserializer = deserialize::Deserializer::new(read::EmbeddedReader::new(reader, scratch));
+	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	Ok(value)
+}
+
+/// Read one data item from `reader` into `buffer` and deserialize it,
+/// borrowing from `buffer`.
+#[cfg(feature = "embedded-io-async")]
+pub async fn from_embedded_async<'b, T, R>(mut reader: R, buffer: &'b mut [u8]) -> Result<T>
+where
+	T: de::Deserialize<'b>,
+	R: embedded_io_async::Read,
+{
+	from_slice(embedded_async::read_item(&mut reader, buffer).await?)
+}
+
+#[cfg(feature = "tokio")]
+pub async fn from_async_reader<T, R>(mut reader: R) -> Result<T>
+where
+	T: de::DeserializeOwned,
+	R: tokio::io::AsyncRead + Unpin,
+{
+	from_slice(&async_io::read_item(&mut reader, None).await?)
+}
+
+#[cfg(feature = "tokio")]
+pub async fn from_async_reader_limit<T, R>(mut reader: R, limit: usize) -> Result<T>
+where
+	T: de::DeserializeOwned,
+	R: tokio::io::AsyncRead + Unpin,
+{
+	from_slice(&async_io::read_item(&mut reader, Some(limit)).await?)
+}
+
 pub fn from_slice<'a, T>(slice: &'a [u8]) -> Result<T>
 where
 	T: de::Deserialize<'a>,
 {
 	let mut deserializer = deserialize::Deserializer::new(read::SliceReader::new(slice));
 	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	deserializer.end()?;
 	Ok(value)
 }
+
+/// Deserialize `slice` into `place`, reusing the allocations it already owns
+/// such as the capacity of its strings, vectors and maps.
+///
+/// Derived types only take advantage of it when `serde_derive` is built with
+/// its `deserialize_in_place` feature. On error, `place` is left in a valid
+/// but unspecified state.
+pub fn from_slice_into<'a, T>(place: &mut T, slice: &'a [u8]) -> Result<()>
+where
+	T: de::Deserialize<'a>,
+{
+	let mut deserializer = deserialize::Deserializer::new(read::SliceReader::new(slice));
+	de::Deserialize::deserialize_in_place(&mut deserializer, place)?;
+	deserializer.end()
+}
+
+pub fn from_slice_prefix<'a, T>(slice: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+	T: de::Deserialize<'a>,
+{
+	let mut deserializer = deserialize::Deserializer::new(read::SliceReader::new(slice));
+	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	Ok((value, &slice[deserializer.offset()..]))
+}
+
+#[cfg(feature = "bytes")]
+pub fn from_bytes<'a, T>(bytes: &'a bytes::Bytes) -> Result<T>
+where
+	T: de::Deserialize<'a>,
+{
+	let mut deserializer = deserialize::Deserializer::new(read::SliceReader::from_bytes(bytes));
+	let value = de::Deserialize::deserialize(&mut deserializer)?;
+	deserializer.end()?;
+	Ok(value)
+}
+
+/// Deserialize the data items of a CBOR sequence (RFC 8742) in parallel.
+///
+/// The boundaries of the items are first found by scanning `input`
+/// without decoding it, then the items are deserialized on the rayon
+/// thread pool. They are yielded in order, each with its offset in `input`.
+/// A malformed or incomplete item is the last one yielded.
+#[cfg(feature = "rayon")]
+pub fn par_iter_sequence<'a, T>(
+	input: &'a [u8],
+) -> impl rayon::iter::IndexedParallelIterator<Item = (usize, Result<T>)> + 'a
+where
+	T: de::Deserialize<'a> + Send,
+{
+	use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+	let mut items = Vec::new();
+	let mut scanner = scan::Scanner::new();
+	let mut offset = 0;
+	while offset < input.len() {
+		match scanner.scan(&input[offset..]) {
+			Ok(scan::Scan::Complete(len)) => {
+				items.push((offset, Ok(len)));
+				offset += len;
+			}
+			Ok(scan::Scan::NeedMore(_)) => {
+				items.push((offset, Err(error::Error::Eof)));
+				break;
+			}
+			Err(error) => {
+				items.push((offset, Err(error)));
+				break;
+			}
+		}
+	}
+	items.into_par_iter().map(move |(offset, len)| {
+		let value = len.and_then(|len| from_slice(&input[offset..offset + len]));
+		(offset, value)
+	})
+}
+
+/// Print the single data item filling `bytes` in extended diagnostic
+/// notation (RFC 8949 section 8), such as `{"a": [1, h'0102', 1_1(0), [_ ]]}`.
+///
+/// Arguments not encoded in the shortest form are followed by their
+/// encoding indicator: `_0` to `_3` for 1 to 8 bytes. Containers and tags
+/// nested deeper than `decode::DEPTH_LIMIT` fail with `Error::Limit`.
+#[cfg(feature = "alloc")]
+pub fn to_diagnostic(bytes: &[u8]) -> Result<alloc::string::String> {
+	diag::print(bytes, false)
+}
+
+/// Like `to_diagnostic`, indenting containers over several lines.
+#[cfg(feature = "alloc")]
+pub fn to_diagnostic_pretty(bytes: &[u8]) -> Result<alloc::string::String> {
+	diag::print(bytes, true)
+}
+
+/// Encode the single data item written in `text` in extended diagnostic
+/// notation, such as `{"a": [1, h'0102', 1(0), _ [1]]}`.
+///
+/// Encoding indicators (`1_1`, `[_0 1]`, `[_ 1]`, `(_ "a", "b")`), comments
+/// (`# ...` and `/ ... /`), embedded data items (`<< 1, 2 >>`) and the `h''`,
+/// `b64''`, `dt''` and `DT''` application strings are supported. Items
+/// nested deeper than `decode::DEPTH_LIMIT` fail with `Error::Limit`.
+#[cfg(feature = "alloc")]
+pub fn from_diagnostic(text: &str) -> Result<Vec<u8>> {
+	edn::parse(text)
+}
+
+/// Annotate each header of the data items in `bytes`, one per line: its
+/// offset, its raw bytes, then its major type and argument, nested items
+/// being indented under their container.
+///
+/// Never fails: everything up to the first malformed or missing byte is
+/// annotated, followed by a line marking where and why decoding stopped.
+#[cfg(feature = "alloc")]
+pub fn annotate(bytes: &[u8]) -> alloc::string::String {
+	annotate::annotate(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn from_slice_rejects_trailing_data() {
+		assert_eq!(from_slice::<u32>(&[0x18, 0x64]).unwrap(), 100);
+		assert!(matches!(
+			from_slice::<u32>(&[0x18, 0x64, 0x00]),
+			Err(error::Error::TrailingData(2))
+		));
+		assert!(matches!(from_slice::<u32>(&[0x18]), Err(error::Error::Eof)));
+	}
+
+	#[test]
+	fn from_slice_prefix_returns_the_rest() {
+		let (value, rest) = from_slice_prefix::<u32>(&[0x01, 0x02, 0x03]).unwrap();
+		assert_eq!(value, 1);
+		assert_eq!(rest, [0x02, 0x03]);
+		let (value, rest) = from_slice_prefix::<&str>(&[0x61, 0x61]).unwrap();
+		assert_eq!(value, "a");
+		assert!(rest.is_empty());
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn append_to_vec_keeps_content_and_capacity() {
+		let mut vec = Vec::with_capacity(64);
+		vec.push(0xFF);
+		let pointer = vec.as_ptr();
+		assert_eq!(append_to_vec(&mut vec, &"abc").unwrap(), 4);
+		assert_eq!(append_to_vec(&mut vec, &[1u8, 2]).unwrap(), 3);
+		assert_eq!(vec, [0xFF, 0x63, 0x61, 0x62, 0x63, 0x82, 0x01, 0x02]);
+		assert_eq!(vec.as_ptr(), pointer);
+		assert_eq!(vec.capacity(), 64);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn to_vec_with_capacity_preallocates() {
+		let vec = to_vec_with_capacity(&1000u32, 32).unwrap();
+		assert_eq!(vec, [0x19, 0x03, 0xE8]);
+		assert!(vec.capacity() >= 32);
+		assert_eq!(to_vec(&1000u32).unwrap(), vec);
+	}
+
+	#[test]
+	fn serialized_size_matches_the_output() {
+		#[derive(serde_derive::Serialize)]
+		enum Shape {
+			Unit,
+			Newtype(i64),
+			Tuple(f32, f64),
+			Struct { name: &'static str, flag: Option<bool> },
+		}
+
+		#[derive(serde_derive::Serialize)]
+		struct Record {
+			id: u64,
+			text: &'static str,
+			shapes: [Shape; 4],
+			bytes: &'static [u8],
+			nothing: (),
+		}
+
+		let long = core::str::from_utf8(&[b'x'; 300]).unwrap();
+		let record = Record {
+			id: u64::MAX,
+			text: long,
+			shapes: [
+				Shape::Unit,
+				Shape::Newtype(-70000),
+				Shape::Tuple(1.5, -0.1),
+				Shape::Struct {
+					name: "é",
+					flag: Some(true),
+				},
+			],
+			bytes: &[0u8; 24],
+			nothing: (),
+		};
+		let mut buffer = [0u8; 512];
+		let len = to_slice(&mut buffer, &record).unwrap();
+		assert_eq!(serialized_size(&record).unwrap(), len);
+		for value in [0u64, 23, 24, 255, 256, 65535, 65536, u32::MAX as u64, u64::MAX] {
+			assert_eq!(serialized_size(&value).unwrap(), to_slice(&mut buffer, &value).unwrap());
+			let negative = -1 - value.min(i64::MAX as u64) as i64;
+			assert_eq!(serialized_size(&negative).unwrap(), to_slice(&mut buffer, &negative).unwrap());
+		}
+		#[cfg(feature = "alloc")]
+		assert_eq!(serialized_size(&record).unwrap(), to_vec(&record).unwrap().len());
+	}
+
+	#[test]
+	fn slice_round_trip() {
+		#[derive(serde_derive::Serialize, serde_derive::Deserialize, PartialEq, Debug)]
+		enum Command<'a> {
+			Stop,
+			Move { x: i16, y: i16 },
+			Say(&'a str),
+		}
+
+		#[derive(serde_derive::Serialize, serde_derive::Deserialize, PartialEq, Debug)]
+		struct Message<'a> {
+			id: u32,
+			#[serde(borrow)]
+			commands: [Command<'a>; 3],
+			payload: [u8; 2],
+			ratio: Option<f32>,
+			ack: bool,
+		}
+
+		let message = Message {
+			id: 70000,
+			commands: [Command::Stop, Command::Move { x: -3, y: 300 }, Command::Say("héllo")],
+			payload: [0xDE, 0xAD],
+			ratio: Some(0.5),
+			ack: false,
+		};
+		let mut buffer = [0u8; 128];
+		let len = to_slice(&mut buffer, &message).unwrap();
+		assert_eq!(from_slice::<Message>(&buffer[..len]).unwrap(), message);
+		assert!(matches!(
+			to_slice(&mut buffer[..len - 1], &message),
+			Err(error::Error::Limit(error::LimitKind::WriteSlice))
+		));
+		assert!(matches!(from_slice::<Message>(&buffer[..len - 1]), Err(error::Error::Eof)));
+	}
+
+	#[cfg(feature = "embedded-io")]
+	#[test]
+	fn embedded_round_trip() {
+		let mut output = [0u8; 8];
+		let len = to_embedded(&mut output[..], &(300u16, 'é')).unwrap();
+		assert_eq!(&output[..len], [0x82, 0x19, 0x01, 0x2C, 0x62, 0xC3, 0xA9]);
+		assert!(to_embedded(&mut output[..4], &(300u16, 'é')).is_err());
+
+		let mut scratch = [0u8; 8];
+		let value: (u16, char) = from_embedded(&output[..len], &mut scratch).unwrap();
+		assert_eq!(value, (300, 'é'));
+		// Every item goes through the scratch slice, which must hold the biggest.
+		let mut scratch = [0u8; 1];
+		let result = from_embedded::<(u16, char), _>(&output[..len], &mut scratch);
+		assert!(matches!(result, Err(error::Error::Limit(error::LimitKind::ReadBuffer))));
+		let mut scratch = [0u8; 8];
+		let result = from_embedded::<(u16, char), _>(&output[..5], &mut scratch);
+		assert!(matches!(result, Err(error::Error::Eof)));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn from_slice_into_reuses_capacity() {
+		use alloc::string::String;
+
+		#[derive(serde_derive::Serialize, serde_derive::Deserialize, PartialEq, Debug)]
+		struct Frame {
+			name: String,
+			samples: Vec<u16>,
+		}
+
+		let mut frame = Frame {
+			name: String::with_capacity(32),
+			samples: Vec::with_capacity(32),
+		};
+		let (name, samples) = (frame.name.as_ptr(), frame.samples.as_ptr());
+		for n in 1..4u16 {
+			let next = Frame {
+				name: "x".repeat(n as usize * 5),
+				samples: (0..n * 5).collect(),
+			};
+			from_slice_into(&mut frame, &to_vec(&next).unwrap()).unwrap();
+			assert_eq!(frame, next);
+			assert_eq!((frame.name.as_ptr(), frame.samples.as_ptr()), (name, samples));
+			assert_eq!((frame.name.capacity(), frame.samples.capacity()), (32, 32));
+		}
+
+		let mut samples = alloc::vec![0u16; 8];
+		let pointer = samples.as_ptr();
+		from_slice_into(&mut samples, &[0x82, 0x01, 0x02]).unwrap();
+		assert_eq!((samples.as_slice(), samples.as_ptr()), (&[1, 2][..], pointer));
+		assert!(from_slice_into(&mut samples, &[0x82, 0x01, 0x02, 0x03]).is_err());
+	}
+
+	/// A path in the temporary directory, unique to this process and `name`.
+	#[cfg(feature = "std")]
+	pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+		std::env::temp_dir().join(alloc::format!("orandja_cbor-{}-{}", std::process::id(), name))
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn file_round_trip() {
+		use alloc::string::String;
+
+		let path = temp_path("file_round_trip");
+		let value = (String::from("abc"), alloc::vec![1u32, 70000]);
+		assert_eq!(to_file(&path, &value).unwrap(), 12);
+		assert_eq!(std::fs::read(&path).unwrap().len(), 12);
+		assert_eq!(from_file::<(String, Vec<u32>), _>(&path).unwrap(), value);
+
+		// Truncated, then followed by trailing data.
+		std::fs::write(&path, [0x82, 0x01]).unwrap();
+		assert!(matches!(from_file::<(u8, u8), _>(&path), Err(error::Error::Eof)));
+		std::fs::write(&path, [0x82, 0x01, 0x02, 0x03]).unwrap();
+		assert!(matches!(from_file::<(u8, u8), _>(&path), Err(error::Error::TrailingData(3))));
+
+		// A huge announced length is not allocated up front.
+		std::fs::write(&path, [0x5B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]).unwrap();
+		assert!(matches!(from_file::<raw::RawCborBuf, _>(&path), Err(error::Error::Eof)));
+
+		std::fs::remove_file(&path).unwrap();
+		let result = from_file::<u8, _>(&path);
+		assert_eq!(result.unwrap_err().classify(), error::ErrorKind::Io);
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn par_iter_sequence_offsets() {
+		use rayon::iter::ParallelIterator;
+
+		// 1, "ab", [2, 3], 1000, then a truncated array.
+		let input = [0x01, 0x62, 0x61, 0x62, 0x82, 0x02, 0x03, 0x19, 0x03, 0xE8, 0x82, 0x04];
+		let items: Vec<(usize, Result<raw::RawCborBuf>)> = par_iter_sequence(&input).collect();
+		let offsets: Vec<usize> = items.iter().map(|(offset, _)| *offset).collect();
+		assert_eq!(offsets, [0, 1, 4, 7, 10]);
+		for (offset, item) in &items[..4] {
+			let bytes = item.as_ref().unwrap().as_bytes();
+			assert_eq!(bytes, &input[*offset..*offset + bytes.len()]);
+		}
+		assert!(matches!(items[4].1, Err(error::Error::Eof)));
+
+		// Typed items, and a malformed one stopping the sequence.
+		let input = [0x01, 0x02, 0xFF, 0x03];
+		let items: Vec<(usize, Result<u8>)> = par_iter_sequence(&input).collect();
+		assert_eq!(items.len(), 3);
+		assert_eq!((items[0].0, items[0].1.as_ref().unwrap()), (0, &1));
+		assert_eq!((items[1].0, items[1].1.as_ref().unwrap()), (1, &2));
+		assert!(matches!(items[2], (2, Err(error::Error::Unexpected(0xFF, _)))));
+
+		assert_eq!(par_iter_sequence::<u8>(&[]).count(), 0);
+	}
+}
+
@@ -0,0 +1,93 @@
+use crate::deserialize::Deserializer;
+use crate::error::*;
+use crate::read::SliceReader;
+use crate::Result;
+use memmap2::Mmap;
+use serde::de;
+use std::fs::File;
+use std::path::Path;
+
+/// A file mapped in memory, the data items decoded from it borrowing
+/// their strings and byte strings directly from the mapping.
+pub struct MappedFile {
+	map: Mmap,
+}
+
+impl MappedFile {
+	/// Map the file at `path` in memory.
+	///
+	/// # Safety
+	///
+	/// The file must not be modified or truncated while it is mapped,
+	/// by this process or any other, see `memmap2::Mmap::map`.
+	pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let file = File::open(path)?;
+		Ok(MappedFile {
+			map: Mmap::map(&file)?,
+		})
+	}
+
+	/// Use a mapping created by the caller.
+	pub fn from_mmap(map: Mmap) -> Self {
+		MappedFile { map }
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.map
+	}
+
+	pub fn into_inner(self) -> Mmap {
+		self.map
+	}
+
+	/// Deserialize the single data item filling the mapping.
+	pub fn deserialize<'a, T>(&'a self) -> Result<T>
+	where
+		T: de::Deserialize<'a>,
+	{
+		crate::from_slice(&self.map)
+	}
+
+	/// Deserialize the data item starting at `offset` in the mapping and
+	/// return it with the offset of the byte following it.
+	pub fn deserialize_at<'a, T>(&'a self, offset: usize) -> Result<(T, usize)>
+	where
+		T: de::Deserialize<'a>,
+	{
+		let slice = self.map.get(offset..).ok_or(Error::Eof)?;
+		let mut deserializer = Deserializer::new(SliceReader::new(slice));
+		let value = de::Deserialize::deserialize(&mut deserializer)?;
+		Ok((value, offset + deserializer.offset()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn items_borrow_from_the_mapping() {
+		let path = crate::tests::temp_path("items_borrow_from_the_mapping");
+		// "abc" then [1, 2] then "d"
+		std::fs::write(&path, [0x63, 0x61, 0x62, 0x63, 0x82, 0x01, 0x02, 0x61, 0x64]).unwrap();
+		let file = unsafe { MappedFile::open(&path) }.unwrap();
+		assert_eq!(file.as_bytes().len(), 9);
+
+		let (text, offset): (&str, _) = file.deserialize_at(0).unwrap();
+		assert_eq!((text, offset), ("abc", 4));
+		assert_eq!(text.as_ptr(), file.as_bytes()[1..].as_ptr());
+		let (pair, offset): ((u8, u8), _) = file.deserialize_at(offset).unwrap();
+		assert_eq!((pair, offset), ((1, 2), 7));
+		let (text, offset): (&str, _) = file.deserialize_at(offset).unwrap();
+		assert_eq!((text, offset), ("d", 9));
+		assert!(matches!(file.deserialize_at::<u8>(offset), Err(Error::Eof)));
+		assert!(matches!(file.deserialize_at::<u8>(10), Err(Error::Eof)));
+		assert!(matches!(file.deserialize::<&str>(), Err(Error::TrailingData(4))));
+
+		std::fs::write(&path, [0x61, 0x65]).unwrap();
+		let file = MappedFile::from_mmap(unsafe { Mmap::map(&File::open(&path).unwrap()) }.unwrap());
+		assert_eq!(file.deserialize::<&str>().unwrap(), "e");
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,259 @@
+use crate::decode::Decoder;
+use crate::read::SliceReader;
+use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::borrow::ToOwned;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::borrow::Borrow;
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::ops::Deref;
+use serde::{de, ser};
+
+/// Name of the newtype used to tell the `Serializer` and the `Deserializer`
+/// that raw bytes are expected.
+pub(crate) const TOKEN: &str = "$__orandja_cbor_private_RawCbor";
+
+/// The exact bytes of one complete data item.
+///
+/// When deserialized, the item is not decoded: its bytes are borrowed
+/// from the input. When serialized, the bytes are written verbatim.
+#[derive(PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct RawCbor {
+	bytes: [u8],
+}
+
+impl RawCbor {
+	/// Check that `bytes` hold exactly one well-formed data item.
+	pub fn from_bytes(bytes: &[u8]) -> Result<&RawCbor> {
+		let mut decoder = Decoder::new(SliceReader::new(bytes));
+		decoder.skip()?;
+		decoder.end()?;
+		Ok(RawCbor::from_bytes_unchecked(bytes))
+	}
+
+	fn from_bytes_unchecked(bytes: &[u8]) -> &RawCbor {
+		unsafe { &*(bytes as *const [u8] as *const RawCbor) }
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.bytes
+	}
+
+	/// Decode the data item.
+	pub fn decode<'a, T>(&'a self) -> Result<T>
+	where
+		T: de::Deserialize<'a>,
+	{
+		crate::from_slice(&self.bytes)
+	}
+}
+
+impl fmt::Debug for RawCbor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "RawCbor({:02x?})", &self.bytes)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl ToOwned for RawCbor {
+	type Owned = RawCborBuf;
+
+	fn to_owned(&self) -> RawCborBuf {
+		RawCborBuf {
+			bytes: self.bytes.to_vec(),
+		}
+	}
+}
+
+impl AsRef<[u8]> for RawCbor {
+	fn as_ref(&self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+/// The owned version of `RawCbor`.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RawCborBuf {
+	bytes: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl RawCborBuf {
+	/// Check that `bytes` hold exactly one well-formed data item.
+	pub fn from_vec(bytes: Vec<u8>) -> Result<RawCborBuf> {
+		RawCbor::from_bytes(&bytes)?;
+		Ok(RawCborBuf { bytes })
+	}
+
+	pub fn into_vec(self) -> Vec<u8> {
+		self.bytes
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for RawCborBuf {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl Deref for RawCborBuf {
+	type Target = RawCbor;
+
+	fn deref(&self) -> &RawCbor {
+		RawCbor::from_bytes_unchecked(&self.bytes)
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl Borrow<RawCbor> for RawCborBuf {
+	fn borrow(&self) -> &RawCbor {
+		self
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl AsRef<[u8]> for RawCborBuf {
+	fn as_ref(&self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> ser::Serialize for RawBytes<'a> {
+	fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+	where
+		S: ser::Serializer,
+	{
+		serializer.serialize_bytes(self.0)
+	}
+}
+
+impl ser::Serialize for RawCbor {
+	fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+	where
+		S: ser::Serializer,
+	{
+		serializer.serialize_newtype_struct(TOKEN, &RawBytes(&self.bytes))
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl ser::Serialize for RawCborBuf {
+	fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+	where
+		S: ser::Serializer,
+	{
+		(**self).serialize(serializer)
+	}
+}
+
+struct BorrowedVisitor;
+
+impl<'de> de::Visitor<'de> for BorrowedVisitor {
+	type Value = &'de RawCbor;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a borrowed raw CBOR data item")
+	}
+
+	fn visit_borrowed_bytes<E: de::Error>(self, bytes: &'de [u8]) -> core::result::Result<Self::Value, E> {
+		Ok(RawCbor::from_bytes_unchecked(bytes))
+	}
+}
+
+impl<'de: 'a, 'a> de::Deserialize<'de> for &'a RawCbor {
+	fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+	where
+		D: de::Deserializer<'de>,
+	{
+		deserializer.deserialize_newtype_struct(TOKEN, BorrowedVisitor)
+	}
+}
+
+#[cfg(feature = "alloc")]
+struct OwnedVisitor;
+
+#[cfg(feature = "alloc")]
+impl<'de> de::Visitor<'de> for OwnedVisitor {
+	type Value = RawCborBuf;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a raw CBOR data item")
+	}
+
+	fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> core::result::Result<Self::Value, E> {
+		Ok(RawCborBuf {
+			bytes: bytes.to_vec(),
+		})
+	}
+
+	fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> core::result::Result<Self::Value, E> {
+		Ok(RawCborBuf { bytes })
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> de::Deserialize<'de> for RawCborBuf {
+	fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+	where
+		D: de::Deserializer<'de>,
+	{
+		deserializer.deserialize_newtype_struct(TOKEN, OwnedVisitor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `[1, [_ 256, (_ h'01'), 1_2(-1)]]`, every header but the first
+	/// carrying an argument or a length to capture.
+	const ITEM: [u8; 18] = [
+		0x82, 0x01, 0x9F, 0x19, 0x01, 0x00, 0x5F, 0x41, 0x01, 0xFF, 0xDA, 0x00, 0x00, 0x00, 0x01, 0x20, 0xFF,
+		0x00,
+	];
+
+	#[test]
+	fn from_bytes_checks_a_single_item() {
+		assert_eq!(RawCbor::from_bytes(&ITEM[2..17]).unwrap().as_bytes(), &ITEM[2..17]);
+		assert!(matches!(RawCbor::from_bytes(&ITEM[2..16]), Err(crate::error::Error::Eof)));
+		assert!(matches!(
+			RawCbor::from_bytes(&ITEM[2..]),
+			Err(crate::error::Error::TrailingData(15))
+		));
+	}
+
+	#[test]
+	fn borrowed_from_a_slice() {
+		let (first, raw): (u32, &RawCbor) = crate::from_slice(&ITEM[..17]).unwrap();
+		assert_eq!(first, 1);
+		assert_eq!(raw.as_bytes(), &ITEM[2..17]);
+		assert!(core::ptr::eq(raw.as_bytes(), &ITEM[2..17]));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn captured_from_io_readers() {
+		let (_, raw): (u32, RawCborBuf) = crate::from_reader(&ITEM[..17]).unwrap();
+		assert_eq!(raw.as_bytes(), &ITEM[2..17]);
+		let input = std::io::BufReader::with_capacity(4, &ITEM[..17]);
+		let (_, raw): (u32, RawCborBuf) = crate::from_bufread(input).unwrap();
+		assert_eq!(raw.as_bytes(), &ITEM[2..17]);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn written_verbatim() {
+		let raw = RawCbor::from_bytes(&ITEM[2..17]).unwrap();
+		assert_eq!(crate::to_vec(&(1, raw)).unwrap(), &ITEM[..17]);
+		assert_eq!(crate::to_vec(&raw.to_owned()).unwrap(), &ITEM[2..17]);
+	}
+}
@@ -1,5 +1,7 @@
 use crate::error::*;
 use crate::Result;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
 use byteorder::{BigEndian, ByteOrder};
 
 pub enum EitherLifetime<'c, 'o> {
@@ -13,8 +15,28 @@ const LENGHT_U32: usize = 4;
 const LENGHT_U64: usize = 8;
 
 pub trait Reader<'r> {
+	/// The last `len` bytes consumed, if they can be borrowed
+	/// for the whole lifetime of the input.
+	fn borrow_last(&self, _len: usize) -> Option<&'r [u8]> {
+		None
+	}
+
 	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>>;
 
+	/// Split the next `size` bytes off the input, sharing its allocation.
+	/// Readers over an input that cannot be shared return `None`
+	/// without consuming anything.
+	#[cfg(feature = "bytes")]
+	fn read_shared(&mut self, _size: usize) -> Result<Option<bytes::Bytes>> {
+		Ok(None)
+	}
+
+	/// Advance over `size` bytes of input without looking at them.
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		self.read_bytes(size)?;
+		Ok(())
+	}
+
 	fn read_u8(&mut self) -> Result<u8> {
 		Ok(match self.read_bytes(LENGHT_U8)? {
 			EitherLifetime::Current(bytes) => bytes[0],
@@ -61,6 +83,8 @@ pub trait Reader<'r> {
 pub struct SliceReader<'r> {
 	slice: &'r [u8],
 	index: usize,
+	#[cfg(feature = "bytes")]
+	parent: Option<&'r bytes::Bytes>,
 }
 
 impl<'r> SliceReader<'r> {
@@ -68,19 +92,36 @@ impl<'r> SliceReader<'r> {
 	fn end(&self, size: usize) -> Result<usize> {
 		match self.index.checked_add(size) {
 			Some(end) if end <= self.slice.len() => Ok(end),
-			_ => Err(Error::Message("Try to read after the end of the slice.")),
+			_ => Err(Error::Eof),
 		}
 	}
 
 	pub fn new(slice: &'r [u8]) -> Self {
 		SliceReader {
-			slice: slice,
+			slice,
 			index: 0,
+			#[cfg(feature = "bytes")]
+			parent: None,
+		}
+	}
+
+	/// Create a reader borrowing from `bytes` that can also share its allocation.
+	#[cfg(feature = "bytes")]
+	pub fn from_bytes(bytes: &'r bytes::Bytes) -> Self {
+		SliceReader {
+			slice: bytes,
+			index: 0,
+			parent: Some(bytes),
 		}
 	}
 }
 
 impl<'r> Reader<'r> for SliceReader<'r> {
+	#[inline]
+	fn borrow_last(&self, len: usize) -> Option<&'r [u8]> {
+		self.slice.get(self.index.checked_sub(len)?..self.index)
+	}
+
 	#[inline]
 	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>> {
 		let end = self.end(size)?;
@@ -88,6 +129,24 @@ impl<'r> Reader<'r> for SliceReader<'r> {
 		self.index = end;
 		Ok(EitherLifetime::Other(bytes))
 	}
+	#[cfg(feature = "bytes")]
+	#[inline]
+	fn read_shared(&mut self, size: usize) -> Result<Option<bytes::Bytes>> {
+		match self.parent {
+			Some(parent) => {
+				let end = self.end(size)?;
+				let shared = parent.slice(self.index..end);
+				self.index = end;
+				Ok(Some(shared))
+			}
+			None => Ok(None),
+		}
+	}
+	#[inline]
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		self.index = self.end(size)?;
+		Ok(())
+	}
 	#[inline]
 	fn read_u8(&mut self) -> Result<u8> {
 		let end = self.end(LENGHT_U8)?;
@@ -132,14 +191,21 @@ impl<'r> Reader<'r> for SliceReader<'r> {
 	}
 }
 
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "std")]
+use std::io::Read;
 
+#[cfg(feature = "std")]
 pub struct IoReader<R: io::Read> {
 	reader: R,
 	scratch: Vec<u8>,
 	limited: bool,
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> IoReader<R> {
 	pub fn new(io: R) -> Self {
 		IoReader {
@@ -158,67 +224,440 @@ impl<R: io::Read> IoReader<R> {
 	}
 
 	#[inline]
-	fn reserve(&mut self, size: usize) -> Result<()> {
-		if size > self.scratch.capacity() {
-			if self.limited {
-				return Err(Error::Message(
-					"Buffer limit exeed reach when reading a io::read element",
-				));
-			} else {
-				self.scratch.reserve(size - self.scratch.capacity());
-			}
+	fn fill(&mut self, size: usize) -> Result<&[u8]> {
+		if size > self.scratch.capacity() && self.limited {
+			return Err(Error::Limit(LimitKind::ReadBuffer));
 		}
-		unsafe { self.scratch.set_len(size) };
-		Ok(())
+		read_scratch(&mut self.reader, &mut self.scratch, size)?;
+		Ok(&self.scratch)
 	}
 }
 
+/// Read exactly `size` bytes into `scratch`.
+///
+/// Beyond its capacity, `scratch` grows as the bytes arrive rather than
+/// to the size announced by the input, which may be arbitrarily large.
+#[cfg(feature = "std")]
+#[inline]
+fn read_scratch<R: io::Read>(mut reader: R, scratch: &mut Vec<u8>, size: usize) -> Result<()> {
+	scratch.clear();
+	if size <= scratch.capacity() {
+		scratch.resize(size, 0);
+		reader.read_exact(scratch)?;
+	} else if reader.take(u64::try_from(size)?).read_to_end(scratch)? < size {
+		return Err(Error::Eof);
+	}
+	Ok(())
+}
+
+#[cfg(feature = "std")]
 impl<'r, R: io::Read> Reader<'r> for IoReader<R> {
 	#[inline]
 	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>> {
-		self.reserve(size)?;
-		self.reader.read(&mut self.scratch).unwrap();
+		Ok(EitherLifetime::Current(self.fill(size)?))
+	}
+
+	#[inline]
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		let size = u64::try_from(size)?;
+		if io::copy(&mut (&mut self.reader).take(size), &mut io::sink())? < size {
+			return Err(Error::Eof);
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn read_u8(&mut self) -> Result<u8> {
+		Ok(self.fill(LENGHT_U8)?[0])
+	}
+	#[inline]
+	fn read_u16(&mut self) -> Result<u16> {
+		Ok(BigEndian::read_u16(self.fill(LENGHT_U16)?))
+	}
+
+	#[inline]
+	fn read_u32(&mut self) -> Result<u32> {
+		Ok(BigEndian::read_u32(self.fill(LENGHT_U32)?))
+	}
+
+	#[inline]
+	fn read_u64(&mut self) -> Result<u64> {
+		Ok(BigEndian::read_u64(self.fill(LENGHT_U64)?))
+	}
+
+	#[inline]
+	fn read_f32(&mut self) -> Result<f32> {
+		Ok(BigEndian::read_f32(self.fill(LENGHT_U32)?))
+	}
+
+	#[inline]
+	fn read_f64(&mut self) -> Result<f64> {
+		Ok(BigEndian::read_f64(self.fill(LENGHT_U64)?))
+	}
+}
+
+/// A reader serving items straight out of the buffer of a `io::BufRead`.
+///
+/// Only the items straddling a refill of the buffer are copied to scratch.
+/// The bytes of the last item read are consumed on the next call, or when
+/// the reader is dropped.
+#[cfg(feature = "std")]
+pub struct BufReadReader<R: io::BufRead> {
+	reader: R,
+	scratch: Vec<u8>,
+	limited: bool,
+	pending: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::BufRead> BufReadReader<R> {
+	pub fn new(io: R) -> Self {
+		BufReadReader {
+			reader: io,
+			scratch: Vec::new(),
+			limited: false,
+			pending: 0,
+		}
+	}
+
+	/// Limit the size of the items which do not fit in the buffer of `io`.
+	pub fn with_limit(io: R, capacity: usize) -> Self {
+		BufReadReader {
+			reader: io,
+			scratch: Vec::with_capacity(capacity),
+			limited: true,
+			pending: 0,
+		}
+	}
+
+	pub fn get_mut(&mut self) -> &mut R {
+		self.consume();
+		&mut self.reader
+	}
+
+	/// Consume the bytes lent out by the last `read_bytes`.
+	#[inline]
+	fn consume(&mut self) {
+		if self.pending > 0 {
+			self.reader.consume(self.pending);
+			self.pending = 0;
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl<R: io::BufRead> Drop for BufReadReader<R> {
+	fn drop(&mut self) {
+		self.consume();
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'r, R: io::BufRead> Reader<'r> for BufReadReader<R> {
+	#[inline]
+	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>> {
+		self.consume();
+		if self.reader.fill_buf()?.len() >= size {
+			self.pending = size;
+			return Ok(EitherLifetime::Current(&self.reader.fill_buf()?[..size]));
+		}
+		if size > self.scratch.capacity() && self.limited {
+			return Err(Error::Limit(LimitKind::ReadBuffer));
+		}
+		read_scratch(&mut self.reader, &mut self.scratch, size)?;
 		Ok(EitherLifetime::Current(&self.scratch))
 	}
 
+	#[inline]
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		self.consume();
+		let mut left = size;
+		while left > 0 {
+			let available = self.reader.fill_buf()?.len();
+			if available == 0 {
+				return Err(Error::Eof);
+			}
+			let len = available.min(left);
+			self.reader.consume(len);
+			left -= len;
+		}
+		Ok(())
+	}
+}
+
+/// A reader over a possibly non-contiguous `bytes::Buf`.
+///
+/// Items fitting in the current chunk are served from it directly,
+/// the others are copied to a scratch buffer. Byte strings decoded with
+/// `buf::shared` are split off the buffer.
+#[cfg(feature = "bytes")]
+pub struct BytesReader<B: bytes::Buf> {
+	buf: B,
+	scratch: Vec<u8>,
+	pending: usize,
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::Buf> BytesReader<B> {
+	pub fn new(buf: B) -> Self {
+		BytesReader {
+			buf,
+			scratch: Vec::new(),
+			pending: 0,
+		}
+	}
+
+	pub fn into_inner(mut self) -> B {
+		self.consume();
+		self.buf
+	}
+
+	/// Advance over the bytes lent out by the last `read_bytes`.
+	#[inline]
+	fn consume(&mut self) {
+		if self.pending > 0 {
+			self.buf.advance(self.pending);
+			self.pending = 0;
+		}
+	}
+
+	#[inline]
+	fn check(&mut self, size: usize) -> Result<()> {
+		self.consume();
+		if self.buf.remaining() < size {
+			return Err(Error::Eof);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(feature = "bytes")]
+impl<'r, B: bytes::Buf> Reader<'r> for BytesReader<B> {
+	#[inline]
+	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>> {
+		self.check(size)?;
+		if self.buf.chunk().len() >= size {
+			self.pending = size;
+			Ok(EitherLifetime::Current(&self.buf.chunk()[..size]))
+		} else {
+			self.scratch.resize(size, 0);
+			self.buf.copy_to_slice(&mut self.scratch);
+			Ok(EitherLifetime::Current(&self.scratch))
+		}
+	}
+
+	#[inline]
+	fn read_shared(&mut self, size: usize) -> Result<Option<bytes::Bytes>> {
+		self.check(size)?;
+		// Free for `Bytes` and `BytesMut`, a copy for the other buffers.
+		Ok(Some(self.buf.copy_to_bytes(size)))
+	}
+
+	#[inline]
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		self.check(size)?;
+		self.buf.advance(size);
+		Ok(())
+	}
+
 	#[inline]
 	fn read_u8(&mut self) -> Result<u8> {
-		self.reserve(LENGHT_U8)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(self.scratch[0])
+		self.check(LENGHT_U8)?;
+		Ok(self.buf.get_u8())
 	}
+
 	#[inline]
 	fn read_u16(&mut self) -> Result<u16> {
-		self.reserve(LENGHT_U16)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(BigEndian::read_u16(&self.scratch))
+		self.check(LENGHT_U16)?;
+		Ok(self.buf.get_u16())
 	}
 
 	#[inline]
 	fn read_u32(&mut self) -> Result<u32> {
-		self.reserve(LENGHT_U32)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(BigEndian::read_u32(&self.scratch))
+		self.check(LENGHT_U32)?;
+		Ok(self.buf.get_u32())
 	}
 
 	#[inline]
 	fn read_u64(&mut self) -> Result<u64> {
-		self.reserve(LENGHT_U64)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(BigEndian::read_u64(&self.scratch))
+		self.check(LENGHT_U64)?;
+		Ok(self.buf.get_u64())
 	}
 
 	#[inline]
 	fn read_f32(&mut self) -> Result<f32> {
-		self.reserve(LENGHT_U32)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(BigEndian::read_f32(&self.scratch))
+		self.check(LENGHT_U32)?;
+		Ok(self.buf.get_f32())
 	}
 
 	#[inline]
 	fn read_f64(&mut self) -> Result<f64> {
-		self.reserve(LENGHT_U64)?;
-		self.reader.read(&mut self.scratch).unwrap();
-		Ok(BigEndian::read_f64(&self.scratch))
+		self.check(LENGHT_U64)?;
+		Ok(self.buf.get_f64())
+	}
+}
+
+/// A reader over an `embedded_io::Read` stream.
+///
+/// Every item is copied to the `scratch` slice given by the caller,
+/// the items bigger than it are rejected.
+#[cfg(feature = "embedded-io")]
+pub struct EmbeddedReader<'s, R: embedded_io::Read> {
+	reader: R,
+	scratch: &'s mut [u8],
+}
+
+#[cfg(feature = "embedded-io")]
+impl<'s, R: embedded_io::Read> EmbeddedReader<'s, R> {
+	pub fn new(io: R, scratch: &'s mut [u8]) -> Self {
+		EmbeddedReader {
+			reader: io,
+			scratch,
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	#[inline]
+	fn fill(&mut self, size: usize) -> Result<&[u8]> {
+		let scratch = self
+			.scratch
+			.get_mut(..size)
+			.ok_or(Error::Limit(LimitKind::ReadBuffer))?;
+		self.reader.read_exact(scratch)?;
+		Ok(scratch)
+	}
+}
+
+#[cfg(feature = "embedded-io")]
+impl<'r, 's, R: embedded_io::Read> Reader<'r> for EmbeddedReader<'s, R> {
+	#[inline]
+	fn read_bytes<'a>(&'a mut self, size: usize) -> Result<EitherLifetime<'a, 'r>> {
+		Ok(EitherLifetime::Current(self.fill(size)?))
+	}
+
+	#[inline]
+	fn skip_bytes(&mut self, size: usize) -> Result<()> {
+		let mut left = size;
+		while left > 0 {
+			let len = left.min(self.scratch.len());
+			if len == 0 {
+				return Err(Error::Limit(LimitKind::ReadBuffer));
+			}
+			self.fill(len)?;
+			left -= len;
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn read_u8(&mut self) -> Result<u8> {
+		Ok(self.fill(LENGHT_U8)?[0])
+	}
+
+	#[inline]
+	fn read_u16(&mut self) -> Result<u16> {
+		Ok(BigEndian::read_u16(self.fill(LENGHT_U16)?))
+	}
+
+	#[inline]
+	fn read_u32(&mut self) -> Result<u32> {
+		Ok(BigEndian::read_u32(self.fill(LENGHT_U32)?))
+	}
+
+	#[inline]
+	fn read_u64(&mut self) -> Result<u64> {
+		Ok(BigEndian::read_u64(self.fill(LENGHT_U64)?))
+	}
+
+	#[inline]
+	fn read_f32(&mut self) -> Result<f32> {
+		Ok(BigEndian::read_f32(self.fill(LENGHT_U32)?))
+	}
+
+	#[inline]
+	fn read_f64(&mut self) -> Result<f64> {
+		Ok(BigEndian::read_f64(self.fill(LENGHT_U64)?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A byte string announcing 2^63 - 1 bytes, and carrying one.
+	#[cfg(feature = "std")]
+	const HUGE: [u8; 10] = [0x5B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+	#[cfg(feature = "std")]
+	fn read_all<'r, R: Reader<'r>>(mut reader: R) -> Result<()> {
+		assert_eq!(reader.read_u8()?, 0x5B);
+		let size = usize::try_from(reader.read_u64()?)?;
+		reader.read_bytes(size)?;
+		Ok(())
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn announced_sizes_are_not_trusted() {
+		assert!(matches!(read_all(SliceReader::new(&HUGE)), Err(Error::Eof)));
+		assert!(matches!(read_all(IoReader::new(&HUGE[..])), Err(Error::Eof)));
+		assert!(matches!(read_all(BufReadReader::new(&HUGE[..])), Err(Error::Eof)));
+		let reader = io::BufReader::with_capacity(4, &HUGE[..]);
+		assert!(matches!(read_all(BufReadReader::new(reader)), Err(Error::Eof)));
+		assert!(matches!(crate::from_reader::<crate::raw::RawCborBuf, _>(&HUGE[..]), Err(Error::Eof)));
+		assert!(matches!(crate::from_bufread::<crate::raw::RawCborBuf, _>(&HUGE[..]), Err(Error::Eof)));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn limits_are_checked_first() {
+		let reader = IoReader::with_limit(&HUGE[..], 16);
+		assert!(matches!(read_all(reader), Err(Error::Limit(LimitKind::ReadBuffer))));
+		let reader = BufReadReader::with_limit(io::BufReader::with_capacity(4, &HUGE[..]), 16);
+		assert!(matches!(read_all(reader), Err(Error::Limit(LimitKind::ReadBuffer))));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn items_straddling_a_refill() {
+		let bytes: alloc::vec::Vec<u8> = (0..40).collect();
+		for capacity in [1, 3, 8, 64] {
+			let mut reader = BufReadReader::new(io::BufReader::with_capacity(capacity, &bytes[..]));
+			assert_eq!(reader.read_u16().unwrap(), 0x0001);
+			match reader.read_bytes(30).unwrap() {
+				EitherLifetime::Current(read) => assert_eq!(read, &bytes[2..32]),
+				EitherLifetime::Other(read) => assert_eq!(read, &bytes[2..32]),
+			}
+			reader.skip_bytes(4).unwrap();
+			assert_eq!(reader.read_u32().unwrap(), 0x24252627);
+			assert!(matches!(reader.read_u8(), Err(Error::Eof)));
+
+			let mut reader = IoReader::new(io::BufReader::with_capacity(capacity, &bytes[..]));
+			reader.skip_bytes(2).unwrap();
+			assert_eq!(reader.read_u64().unwrap(), 0x0203040506070809);
+			match reader.read_bytes(30).unwrap() {
+				EitherLifetime::Current(read) => assert_eq!(read, &bytes[10..]),
+				EitherLifetime::Other(read) => assert_eq!(read, &bytes[10..]),
+			}
+			assert!(matches!(reader.skip_bytes(1), Err(Error::Eof)));
+		}
+	}
+
+	#[test]
+	fn slice_reader_borrows() {
+		let bytes = [0x01, 0x02, 0x03];
+		let mut reader = SliceReader::new(&bytes);
+		reader.skip_bytes(1).unwrap();
+		match reader.read_bytes(2).unwrap() {
+			EitherLifetime::Other(read) => assert_eq!(read.as_ptr(), bytes[1..].as_ptr()),
+			EitherLifetime::Current(_) => panic!("slice copied"),
+		}
+		assert_eq!(reader.borrow_last(3), Some(&bytes[..]));
+		assert_eq!(reader.borrow_last(2), Some(&bytes[1..]));
+		assert_eq!(reader.borrow_last(4), None);
+		assert!(matches!(reader.read_bytes(usize::MAX), Err(Error::Eof)));
 	}
 }
@@ -0,0 +1,466 @@
+use crate::cbor::*;
+use crate::decode::DEPTH_LIMIT;
+use crate::error::*;
+use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use core::convert::TryFrom;
+
+/// The state of a `Scanner` after looking at a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scan {
+	/// A complete data item fills the first `n` bytes of the buffer.
+	Complete(usize),
+	/// At least `n` more bytes are needed to complete the data item.
+	NeedMore(usize),
+}
+
+/// A container opened and not yet completed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Frame {
+	/// `n` items are still expected.
+	Definite(u64),
+	/// Items are expected until a break.
+	Indefinite,
+	/// Keys and values are expected until a break, `true` while a key
+	/// waits for its value.
+	IndefiniteMap(bool),
+	/// Definite strings of the given major type are expected until a break.
+	Chunks(u8),
+}
+
+/// Maximum nesting of containers of a `Scanner` without the `alloc` feature.
+#[cfg(not(feature = "alloc"))]
+pub const SCANNER_DEPTH: usize = 32;
+
+/// The open containers, the innermost last.
+#[derive(Debug, Clone)]
+pub(crate) struct Frames {
+	#[cfg(feature = "alloc")]
+	frames: Vec<Frame>,
+	#[cfg(not(feature = "alloc"))]
+	frames: [Frame; SCANNER_DEPTH],
+	#[cfg(not(feature = "alloc"))]
+	len: usize,
+	limit: usize,
+}
+
+impl Frames {
+	/// An empty stack holding at most `limit` frames,
+	/// and never more than `SCANNER_DEPTH` without the `alloc` feature.
+	#[cfg(feature = "alloc")]
+	pub(crate) fn new(limit: usize) -> Self {
+		Frames {
+			frames: Vec::new(),
+			limit,
+		}
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	pub(crate) fn new(limit: usize) -> Self {
+		Frames {
+			frames: [Frame::Definite(0); SCANNER_DEPTH],
+			len: 0,
+			limit,
+		}
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	fn as_slice(&self) -> &[Frame] {
+		&self.frames
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	fn as_slice(&self) -> &[Frame] {
+		&self.frames[..self.len]
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	pub(crate) fn len(&self) -> usize {
+		self.as_slice().len()
+	}
+
+	#[inline]
+	pub(crate) fn is_empty(&self) -> bool {
+		self.as_slice().is_empty()
+	}
+
+	#[inline]
+	pub(crate) fn last(&self) -> Option<&Frame> {
+		self.as_slice().last()
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	fn last_mut(&mut self) -> Option<&mut Frame> {
+		self.frames.last_mut()
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	fn last_mut(&mut self) -> Option<&mut Frame> {
+		self.frames[..self.len].last_mut()
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	pub(crate) fn push(&mut self, frame: Frame) -> Result<()> {
+		if self.frames.len() >= self.limit {
+			return Err(Error::Limit(LimitKind::Depth));
+		}
+		self.frames.push(frame);
+		Ok(())
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	pub(crate) fn push(&mut self, frame: Frame) -> Result<()> {
+		if self.len >= self.limit {
+			return Err(Error::Limit(LimitKind::Depth));
+		}
+		*self.frames.get_mut(self.len).ok_or(Error::Limit(LimitKind::Depth))? = frame;
+		self.len += 1;
+		Ok(())
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	pub(crate) fn pop(&mut self) {
+		self.frames.pop();
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	pub(crate) fn pop(&mut self) {
+		self.len = self.len.saturating_sub(1);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[inline]
+	fn clear(&mut self) {
+		self.frames.clear();
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[inline]
+	fn clear(&mut self) {
+		self.len = 0;
+	}
+
+	/// Count one item in the innermost container.
+	#[inline]
+	pub(crate) fn count_item(&mut self) {
+		match self.last_mut() {
+			Some(Frame::Definite(remaining)) => *remaining -= 1,
+			Some(Frame::IndefiniteMap(key)) => *key = !*key,
+			_ => {}
+		}
+	}
+
+	/// Close every innermost definite container that is complete.
+	#[inline]
+	pub(crate) fn close_complete(&mut self) {
+		while let Some(Frame::Definite(0)) = self.last() {
+			self.pop();
+		}
+	}
+
+	/// Account for the header made of `initial` and `argument` in the open
+	/// containers, rejecting it where it cannot appear. Returns the length
+	/// of the payload following it, zero if none.
+	pub(crate) fn accept(&mut self, initial: u8, argument: u64) -> Result<u64> {
+		let major = initial >> 5;
+		let info = initial & 0x1F;
+		if initial == HEADER_BREAK {
+			match self.last() {
+				Some(Frame::Indefinite) | Some(Frame::Chunks(_)) | Some(Frame::IndefiniteMap(false)) => self.pop(),
+				Some(Frame::IndefiniteMap(true)) => return Err(Error::Unexpected(HEADER_BREAK, "map value")),
+				_ => return Err(Error::Unexpected(HEADER_BREAK, "any other header")),
+			}
+			self.close_complete();
+			return Ok(0);
+		}
+		if let Some(Frame::Chunks(chunk)) = self.last() {
+			if major != *chunk || info == SIZE_INFINITE {
+				return Err(Error::Unexpected(initial, "definite string chunk"));
+			}
+		}
+		let mut payload = 0;
+		let frame = match (major, info) {
+			(MAJOR_BYTE, SIZE_INFINITE) | (MAJOR_TEXT, SIZE_INFINITE) => Some(Frame::Chunks(major)),
+			(MAJOR_ARRAY, SIZE_INFINITE) => Some(Frame::Indefinite),
+			(MAJOR_MAP, SIZE_INFINITE) => Some(Frame::IndefiniteMap(false)),
+			(_, SIZE_INFINITE) => return Err(Error::Unassigned(initial)),
+			(MAJOR_BYTE, _) | (MAJOR_TEXT, _) => {
+				payload = argument;
+				None
+			}
+			(MAJOR_ARRAY, _) if argument > 0 => Some(Frame::Definite(argument)),
+			(MAJOR_MAP, _) if argument > 0 => {
+				let items = argument.checked_mul(2).ok_or(Error::Limit(LimitKind::Index))?;
+				Some(Frame::Definite(items))
+			}
+			(MAJOR_TAG, _) => Some(Frame::Definite(1)),
+			(MAJOR_PRIMITIVE, SIZE_8) if argument < 32 => return Err(Error::Unassigned(initial)),
+			_ => None,
+		};
+		self.count_item();
+		if let Some(frame) = frame {
+			self.push(frame)?;
+		}
+		self.close_complete();
+		Ok(payload)
+	}
+}
+
+/// Number of bytes following an `initial` byte to hold its argument.
+#[inline]
+pub(crate) fn argument_size(initial: u8) -> Result<usize> {
+	match initial & 0x1F {
+		info if info < SIZE_8 => Ok(0),
+		SIZE_8 => Ok(1),
+		SIZE_16 => Ok(2),
+		SIZE_32 => Ok(4),
+		SIZE_64 => Ok(8),
+		SIZE_INFINITE => Ok(0),
+		_ => Err(Error::Unassigned(initial)),
+	}
+}
+
+/// Read the header at `offset` of `buffer`: its initial byte, its argument
+/// and its size, initial byte included. `None` if `buffer` ends before.
+///
+/// The argument of an indefinite length or a break is `SIZE_INFINITE`.
+#[inline]
+pub(crate) fn header(buffer: &[u8], offset: usize) -> Result<Option<(u8, u64, usize)>> {
+	let initial = match buffer.get(offset) {
+		Some(initial) => *initial,
+		None => return Ok(None),
+	};
+	let size = argument_size(initial)?;
+	let bytes = match buffer.get(offset + 1..offset + 1 + size) {
+		Some(bytes) => bytes,
+		None => return Ok(None),
+	};
+	let argument = match size {
+		0 => (initial & 0x1F) as u64,
+		1 => bytes[0] as u64,
+		2 => BigEndian::read_u16(bytes) as u64,
+		4 => BigEndian::read_u32(bytes) as u64,
+		_ => BigEndian::read_u64(bytes),
+	};
+	Ok(Some((initial, argument, 1 + size)))
+}
+
+/// An incremental well-formedness checker.
+///
+/// The scanner is given the same growing buffer again and again and
+/// resumes where it stopped, nothing is decoded and no byte is copied.
+/// Once a data item is complete, the scanner is reset and the next call
+/// expects a buffer starting with the next data item.
+///
+/// Containers and tags can be nested `decode::DEPTH_LIMIT` deep by default,
+/// and less than `SCANNER_DEPTH` deep without the `alloc` feature.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+	offset: usize,
+	frames: Frames,
+}
+
+impl Scanner {
+	pub fn new() -> Self {
+		let mut scanner = Scanner {
+			offset: 0,
+			// The data item itself takes the first frame.
+			frames: Frames::new(DEPTH_LIMIT + 1),
+		};
+		scanner.reset();
+		scanner
+	}
+
+	/// Limit the nesting of containers and tags to `limit` levels, deeper
+	/// items being rejected with `Error::Limit(LimitKind::Depth)`.
+	/// `decode::DEPTH_LIMIT` by default.
+	pub fn set_depth_limit(&mut self, limit: usize) {
+		self.frames.limit = limit.saturating_add(1);
+	}
+
+	pub fn depth_limit(&self) -> usize {
+		self.frames.limit - 1
+	}
+
+	/// Forget the progress made on the current data item.
+	pub fn reset(&mut self) {
+		self.offset = 0;
+		self.frames.clear();
+		// A single frame cannot exceed any depth.
+		let _ = self.frames.push(Frame::Definite(1));
+	}
+
+	/// Number of bytes of the buffer already known to be well-formed.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// Look at `buffer` from where the previous call stopped.
+	pub fn scan(&mut self, buffer: &[u8]) -> Result<Scan> {
+		while !self.frames.is_empty() {
+			let (initial, argument, size) = match header(buffer, self.offset)? {
+				Some(header) => header,
+				None => {
+					let size = match buffer.get(self.offset) {
+						Some(initial) => 1 + argument_size(*initial)?,
+						None => 1,
+					};
+					return Ok(Scan::NeedMore(self.offset + size - buffer.len()));
+				}
+			};
+			let end = self.offset + size;
+			// The payload must be there before the header is accounted for,
+			// the next call reading the header again.
+			let major = initial >> 5;
+			if initial & 0x1F != SIZE_INFINITE && (major == MAJOR_BYTE || major == MAJOR_TEXT) {
+				let payload = usize::try_from(argument)?;
+				if end.checked_add(payload).ok_or(Error::Limit(LimitKind::Index))? > buffer.len() {
+					return Ok(Scan::NeedMore(end + payload - buffer.len()));
+				}
+			}
+			let payload = self.frames.accept(initial, argument)?;
+			self.offset = end + payload as usize;
+		}
+		let len = self.offset;
+		self.reset();
+		Ok(Scan::Complete(len))
+	}
+}
+
+impl Default for Scanner {
+	fn default() -> Self {
+		Scanner::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// [256, h'616263', (_ "a"), 1(null)]
+	const ITEM: [u8; 15] = [
+		0x84, 0x19, 0x01, 0x00, 0x43, 0x61, 0x62, 0x63, 0x7F, 0x61, 0x61, 0xFF, 0xC1, 0xF6, 0x00,
+	];
+
+	/// What a scanner reports for each prefix of `ITEM`.
+	const PREFIXES: [Scan; 15] = [
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(2),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(3),
+		Scan::NeedMore(2),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::NeedMore(1),
+		Scan::Complete(14),
+	];
+
+	#[test]
+	fn need_more_at_each_byte() {
+		let mut scanner = Scanner::new();
+		for (len, expected) in PREFIXES.iter().enumerate() {
+			assert_eq!(Scanner::new().scan(&ITEM[..len]).unwrap(), *expected, "fresh, {} bytes", len);
+			assert_eq!(scanner.scan(&ITEM[..len]).unwrap(), *expected, "resumed, {} bytes", len);
+		}
+		assert_eq!(Scanner::new().scan(&ITEM).unwrap(), Scan::Complete(14));
+	}
+
+	#[test]
+	fn headers() {
+		let buffer = [0x17, 0x19, 0x01, 0x00, 0x5F, 0xFB, 0, 0, 0, 0, 0, 0, 0, 0x01];
+		assert_eq!(header(&buffer, 0).unwrap(), Some((0x17, 23, 1)));
+		assert_eq!(header(&buffer, 1).unwrap(), Some((0x19, 256, 3)));
+		assert_eq!(header(&buffer, 4).unwrap(), Some((0x5F, SIZE_INFINITE as u64, 1)));
+		assert_eq!(header(&buffer, 5).unwrap(), Some((0xFB, 1, 9)));
+		assert_eq!(header(&buffer[..13], 5).unwrap(), None);
+		assert_eq!(header(&buffer, 14).unwrap(), None);
+		assert!(matches!(header(&[0x1C], 0), Err(Error::Unassigned(0x1C))));
+	}
+
+	#[test]
+	fn offset_and_reset() {
+		let mut scanner = Scanner::new();
+		assert_eq!(scanner.scan(&ITEM[..6]).unwrap(), Scan::NeedMore(2));
+		assert_eq!(scanner.offset(), 4);
+		scanner.reset();
+		assert_eq!(scanner.offset(), 0);
+		assert_eq!(scanner.scan(&ITEM[14..]).unwrap(), Scan::Complete(1));
+		// A complete item resets the scanner for the next one.
+		assert_eq!(scanner.offset(), 0);
+		assert_eq!(scanner.scan(&[0x01]).unwrap(), Scan::Complete(1));
+	}
+
+	#[test]
+	fn malformed_items() {
+		let cases: &[(&[u8], u8)] = &[
+			(&[0xFF], HEADER_BREAK),
+			(&[0x82, 0x01, 0xFF], HEADER_BREAK),
+			(&[0x7F, 0x41, 0x00], 0x41),
+			(&[0x5F, 0x5F], 0x5F),
+		];
+		for (bytes, initial) in cases {
+			assert!(matches!(Scanner::new().scan(bytes), Err(Error::Unexpected(i, _)) if i == *initial));
+		}
+		for bytes in [&[0x1C][..], &[0x3F], &[0xDF], &[0xF8, 0x10]] {
+			assert!(matches!(Scanner::new().scan(bytes), Err(Error::Unassigned(i)) if i == bytes[0]));
+		}
+		let huge = [0x5B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+		assert!(Scanner::new().scan(&huge).is_err());
+	}
+
+	#[test]
+	fn indefinite_maps_pair_keys_and_values() {
+		assert_eq!(Scanner::new().scan(&[0xBF, 0x01, 0x02, 0xFF]).unwrap(), Scan::Complete(4));
+		assert_eq!(Scanner::new().scan(&[0xBF, 0x01, 0x9F, 0xFF, 0xFF]).unwrap(), Scan::Complete(5));
+		assert!(matches!(Scanner::new().scan(&[0xBF, 0x01, 0xFF]), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(Scanner::new().scan(&[0xBF, 0x01, 0x02, 0x03, 0xFF]), Err(Error::Unexpected(HEADER_BREAK, _))));
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let mut deep = [0x81; DEPTH_LIMIT + 1];
+		deep[DEPTH_LIMIT] = 0x00;
+		let mut scanner = Scanner::new();
+		assert_eq!(scanner.depth_limit(), DEPTH_LIMIT);
+		scanner.set_depth_limit(3);
+		assert!(matches!(scanner.scan(&deep[DEPTH_LIMIT - 4..]), Err(Error::Limit(LimitKind::Depth))));
+		scanner.reset();
+		assert_eq!(scanner.scan(&deep[DEPTH_LIMIT - 3..]).unwrap(), Scan::Complete(4));
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn depth_is_limited_with_alloc() {
+		let mut deep = [0x81; DEPTH_LIMIT + 1];
+		deep[DEPTH_LIMIT] = 0x00;
+		assert_eq!(Scanner::new().scan(&deep).unwrap(), Scan::Complete(DEPTH_LIMIT + 1));
+		let deeper = [0xC1; 100_000];
+		assert!(matches!(Scanner::new().scan(&deeper), Err(Error::Limit(LimitKind::Depth))));
+	}
+
+	#[cfg(not(feature = "alloc"))]
+	#[test]
+	fn depth_is_limited_without_alloc() {
+		// The data item itself takes the first frame.
+		let deep = [0x81; SCANNER_DEPTH];
+		assert!(matches!(Scanner::new().scan(&deep), Err(Error::Limit(LimitKind::Depth))));
+		assert_eq!(Scanner::new().scan(&deep[..SCANNER_DEPTH - 1]).unwrap(), Scan::NeedMore(1));
+	}
+}
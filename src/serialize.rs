@@ -1,70 +1,36 @@
 use crate::cbor::*;
+use crate::encode::Encoder;
 use crate::error::*;
 use crate::write::*;
 use crate::Result;
-use byteorder::{BigEndian, ByteOrder};
 use serde::ser;
-use std::convert::TryFrom;
+use core::convert::TryFrom;
 
 type Ok = usize;
 
 pub struct Serializer<W: Writer> {
-	writer: W,
-	buffer: [u8; 9],
-	len_buffer: usize,
+	encoder: Encoder<W>,
+	raw: bool,
 }
 
 impl<W: Writer> Serializer<W> {
 	pub fn new(writer: W) -> Self {
 		Serializer {
-			writer: writer,
-			buffer: [0u8; 9],
-			len_buffer: 0,
+			encoder: Encoder::new(writer),
+			raw: false,
 		}
 	}
 
-	#[inline]
-	fn write_header_u8(&mut self, major: u8, header_value: u8) -> Result<Ok> {
-		if header_value < SIZE_8 {
-			self.buffer[0] = major << 5 | header_value;
-			self.writer.write(&self.buffer[..1])
-		} else {
-			self.buffer[0] = major << 5 | SIZE_8;
-			self.buffer[1] = header_value;
-			self.writer.write(&self.buffer[..2])
-		}
-	}
-
-	#[inline]
-	fn write_header_u16(&mut self, major: u8, header_value: u16) -> Result<Ok> {
-		if header_value <= core::u8::MAX as u16 {
-			self.write_header_u8(major, header_value as u8)
-		} else {
-			self.buffer[0] = major << 5 | SIZE_16;
-			BigEndian::write_u16(&mut self.buffer[1..], header_value);
-			self.writer.write(&self.buffer[..3])
-		}
+	pub fn into_inner(self) -> W {
+		self.encoder.into_inner()
 	}
 
 	#[inline]
-	fn write_header_u32(&mut self, major: u8, header_value: u32) -> Result<Ok> {
-		if header_value <= core::u16::MAX as u32 {
-			self.write_header_u16(major, header_value as u16)
-		} else {
-			self.buffer[0] = major << 5 | SIZE_32;
-			BigEndian::write_u32(&mut self.buffer[1..], header_value);
-			self.writer.write(&self.buffer[..5])
-		}
-	}
-
-	#[inline]
-	fn write_header_u64(&mut self, major: u8, header_value: u64) -> Result<Ok> {
-		if header_value <= core::u32::MAX as u64 {
-			self.write_header_u32(major, header_value as u32)
+	fn push_int(&mut self, value: i64) -> Result<Ok> {
+		if value.is_negative() {
+			self.encoder.push_negint(u64::try_from(-(value + 1))?)
 		} else {
-			self.buffer[0] = major << 5 | SIZE_64;
-			BigEndian::write_u64(&mut self.buffer[1..], header_value);
-			self.writer.write(&self.buffer)
+			self.encoder.push_uint(value as u64)
 		}
 	}
 }
@@ -83,120 +49,95 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 
 	#[inline]
 	fn serialize_bool(self, value: bool) -> Result<Self::Ok> {
-		self.buffer[0] = if value { HEADER_TRUE } else { HEADER_FALSE };
-		self.writer.write(&self.buffer[..1])
+		self.encoder.push_simple(if value { SIMPLE_TRUE } else { SIMPLE_FALSE })
 	}
 
 	#[inline]
 	fn serialize_i8(self, value: i8) -> Result<Self::Ok> {
-		if value.is_negative() {
-			self.write_header_u8(MAJOR_NEGATIVE, u8::try_from(-(value + 1))?)
-		} else {
-			self.write_header_u8(MAJOR_POSITIVE, value as u8)
-		}
+		self.push_int(value as i64)
 	}
 
 	#[inline]
 	fn serialize_i16(self, value: i16) -> Result<Self::Ok> {
-		if value.is_negative() {
-			self.write_header_u16(MAJOR_NEGATIVE, u16::try_from(-(value + 1))?)
-		} else {
-			self.write_header_u16(MAJOR_POSITIVE, value as u16)
-		}
+		self.push_int(value as i64)
 	}
 
 	#[inline]
 	fn serialize_i32(self, value: i32) -> Result<Self::Ok> {
-		if value.is_negative() {
-			self.write_header_u32(MAJOR_NEGATIVE, u32::try_from(-(value + 1))?)
-		} else {
-			self.write_header_u32(MAJOR_POSITIVE, value as u32)
-		}
+		self.push_int(value as i64)
 	}
 
 	#[inline]
 	fn serialize_i64(self, value: i64) -> Result<Self::Ok> {
-		if value.is_negative() {
-			self.write_header_u64(MAJOR_NEGATIVE, u64::try_from(-(value + 1))?)
-		} else {
-			self.write_header_u64(MAJOR_POSITIVE, value as u64)
-		}
+		self.push_int(value)
 	}
 
 	#[inline]
 	fn serialize_u8(self, value: u8) -> Result<Self::Ok> {
-		self.write_header_u8(MAJOR_POSITIVE, value)
+		self.encoder.push_uint(value as u64)
 	}
 
 	#[inline]
 	fn serialize_u16(self, value: u16) -> Result<Self::Ok> {
-		self.write_header_u16(MAJOR_POSITIVE, value)
+		self.encoder.push_uint(value as u64)
 	}
 
 	#[inline]
 	fn serialize_u32(self, value: u32) -> Result<Self::Ok> {
-		self.write_header_u32(MAJOR_POSITIVE, value)
+		self.encoder.push_uint(value as u64)
 	}
 
 	#[inline]
 	fn serialize_u64(self, value: u64) -> Result<Self::Ok> {
-		self.write_header_u64(MAJOR_POSITIVE, value)
+		self.encoder.push_uint(value)
 	}
 
 	#[inline]
 	fn serialize_f32(self, value: f32) -> Result<Self::Ok> {
-		self.buffer[0] = HEADER_FLOAT_32;
-		BigEndian::write_f32(&mut self.buffer[1..], value);
-		self.writer.write(&self.buffer[..5])
+		self.encoder.push_f32(value)
 	}
 
 	#[inline]
 	fn serialize_f64(self, value: f64) -> Result<Self::Ok> {
-		self.buffer[0] = HEADER_FLOAT_64;
-		BigEndian::write_f64(&mut self.buffer[1..], value);
-		self.writer.write(&self.buffer)
+		self.encoder.push_float(value)
 	}
 
 	#[inline]
 	fn serialize_char(self, value: char) -> Result<Self::Ok> {
-		value.encode_utf8(&mut self.buffer[1..5]);
-		self.len_buffer = value.len_utf8();
-		self.buffer[0] = HEADER_TEXT_START | self.len_buffer as u8;
-		self.writer.write(&self.buffer[..=self.len_buffer])
+		let mut buffer = [0u8; 4];
+		self.encoder.push_text(value.encode_utf8(&mut buffer))
 	}
 
 	#[inline]
 	fn serialize_str(self, value: &str) -> Result<Self::Ok> {
-		self.len_buffer = self.write_header_u64(MAJOR_TEXT, value.len() as u64)?;
-		self.len_buffer += self.writer.write(value.as_bytes())?;
-		Ok(self.len_buffer)
+		self.encoder.push_text(value)
 	}
 
 	#[inline]
 	fn serialize_bytes(self, value: &[u8]) -> Result<Self::Ok> {
-		self.len_buffer = self.write_header_u64(MAJOR_BYTE, value.len() as u64)?;
-		self.len_buffer += self.writer.write(value)?;
-		Ok(self.len_buffer)
+		if self.raw {
+			self.raw = false;
+			return self.encoder.push_raw(value);
+		}
+		self.encoder.push_bytes(value)
 	}
 
 	#[inline]
 	fn serialize_none(self) -> Result<Self::Ok> {
-		self.buffer[0] = HEADER_NULL;
-		self.writer.write(&self.buffer[..1])
+		self.encoder.push_simple(SIMPLE_NULL)
 	}
 
 	#[inline]
-	fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
+	fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		value.serialize(self)
 	}
 
 	#[inline]
 	fn serialize_unit(self) -> Result<Self::Ok> {
-		self.buffer[0] = HEADER_UNDEFINED;
-		self.writer.write(&self.buffer[..1])
+		self.encoder.push_simple(SIMPLE_UNDEFINED)
 	}
 
 	#[inline]
@@ -205,39 +146,29 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 	}
 
 	#[inline]
-	fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+	fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
-		value.serialize(self)
+		self.raw = name == crate::raw::TOKEN;
+		let result = value.serialize(&mut *self);
+		self.raw = false;
+		result
 	}
 
 	#[inline]
 	fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-		match len {
-			Some(len) => {
-				let lenght = self.write_header_u64(MAJOR_ARRAY, len as u64)?;
-				Ok(SerializeSeq {
-					se: self,
-					serialize_len: lenght,
-					end_marker: false,
-				})
-			}
-			None => {
-				self.buffer[0] = HEADER_ARRAY_INFINITE;
-				let lenght = self.writer.write(&self.buffer[..1])?;
-				Ok(SerializeSeq {
-					se: self,
-					serialize_len: lenght,
-					end_marker: true,
-				})
-			},
-		}
+		let lenght = self.encoder.begin_array(len.map(|len| len as u64))?;
+		Ok(SerializeSeq {
+			se: self,
+			serialize_len: lenght,
+			end_marker: len.is_none(),
+		})
 	}
 
 	#[inline]
 	fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-		let lenght = self.write_header_u64(MAJOR_ARRAY, len as u64)?;
+		let lenght = self.encoder.begin_array(Some(len as u64))?;
 		Ok(SerializeTuple {
 			se: self,
 			serialize_len: lenght,
@@ -250,7 +181,7 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 		_name: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleStruct> {
-		let lenght = self.write_header_u64(MAJOR_ARRAY, len as u64)?;
+		let lenght = self.encoder.begin_array(Some(len as u64))?;
 		Ok(SerializeTupleStruct {
 			se: self,
 			serialize_len: lenght,
@@ -259,33 +190,20 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 
 	#[inline]
 	fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-		match len {
-			Some(len) => {
-				let len = self.write_header_u64(MAJOR_MAP, len as u64)?;
-				Ok(SerializeMap {
-					se: self,
-					serialize_len: len,
-					end_marker: false
-				})
-			}
-			None => {
-				self.buffer[0] = HEADER_MAP_INFINITE;
-				let lenght = self.writer.write(&self.buffer[..1])?;
-				Ok(SerializeMap {
-					se: self,
-					serialize_len: lenght,
-					end_marker: true,
-				})
-			},
-		}
+		let lenght = self.encoder.begin_map(len.map(|len| len as u64))?;
+		Ok(SerializeMap {
+			se: self,
+			serialize_len: lenght,
+			end_marker: len.is_none(),
+		})
 	}
 
 	#[inline]
 	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-		self.len_buffer = self.write_header_u64(MAJOR_MAP, len as u64)?;
+		let lenght = self.encoder.begin_map(Some(len as u64))?;
 		Ok(SerializeStruct {
 			se: self,
-			serialize_len: len,
+			serialize_len: lenght,
 		})
 	}
 
@@ -300,7 +218,7 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 	}
 
 	#[inline]
-	fn serialize_newtype_variant<T: ?Sized>(
+	fn serialize_newtype_variant<T>(
 		self,
 		_name: &'static str,
 		_variant_index: u32,
@@ -308,10 +226,9 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 		value: &T,
 	) -> Result<Self::Ok>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
-		self.buffer[0] = HEADER_MAP_START | 1;
-		let mut lenght = self.writer.write(&self.buffer[..1])?;
+		let mut lenght = self.encoder.begin_map(Some(1))?;
 		lenght += self.serialize_str(variant)?;
 		lenght += value.serialize(self)?;
 		Ok(lenght)
@@ -325,10 +242,9 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeTupleVariant> {
-		self.buffer[0] = HEADER_MAP_START | 1;
-		let mut lenght = self.writer.write(&self.buffer[..1])?;
+		let mut lenght = self.encoder.begin_map(Some(1))?;
 		lenght += self.serialize_str(variant)?;
-		lenght += self.write_header_u64(MAJOR_ARRAY, len as u64)?;
+		lenght += self.encoder.begin_array(Some(len as u64))?;
 
 		Ok(SerializeTupleVariant {
 			se: self,
@@ -344,22 +260,90 @@ impl<'a, W: Writer> ser::Serializer for &'a mut Serializer<W> {
 		variant: &'static str,
 		len: usize,
 	) -> Result<Self::SerializeStructVariant> {
-		self.buffer[0] = HEADER_MAP_START | 1;
-		let mut lenght = self.writer.write(&self.buffer[..1])?;
+		let mut lenght = self.encoder.begin_map(Some(1))?;
 		lenght += self.serialize_str(variant)?;
-		lenght += self.write_header_u64(MAJOR_MAP, len as u64)?;
+		lenght += self.encoder.begin_map(Some(len as u64))?;
 		Ok(SerializeStructVariant {
 			se: self,
 			serialize_len: lenght,
 		})
 	}
 
+	/// Without an allocator, `value` is formatted twice:
+	/// once to know its length and once to write it.
+	#[cfg(not(feature = "alloc"))]
+	fn collect_str<T>(self, value: &T) -> Result<Self::Ok>
+	where
+		T: ?Sized + core::fmt::Display,
+	{
+		let mut counter = Counter(0);
+		let _ = core::fmt::write(&mut counter, format_args!("{}", value));
+		let mut payload = Payload {
+			len: self.encoder.begin_text(counter.0 as u64)?,
+			left: counter.0,
+			encoder: &mut self.encoder,
+			error: None,
+		};
+		let result = core::fmt::write(&mut payload, format_args!("{}", value));
+		match (payload.error, result) {
+			(Some(error), _) => Err(error),
+			(None, Err(_)) => Err(ser::Error::custom("a Display implementation returned an error")),
+			(None, Ok(())) if payload.left > 0 => Err(ser::Error::custom(UNSTABLE_DISPLAY)),
+			(None, Ok(())) => Ok(payload.len),
+		}
+	}
+
 	#[inline]
 	fn is_human_readable(&self) -> bool {
 		false
 	}
 }
 
+#[cfg(not(feature = "alloc"))]
+const UNSTABLE_DISPLAY: &str = "a Display implementation formatted two different lengths";
+
+/// Count the bytes of a formatted value.
+#[cfg(not(feature = "alloc"))]
+struct Counter(usize);
+
+#[cfg(not(feature = "alloc"))]
+impl core::fmt::Write for Counter {
+	fn write_str(&mut self, s: &str) -> core::fmt::Result {
+		self.0 += s.len();
+		Ok(())
+	}
+}
+
+/// Write a formatted value as the content of a text string of `left` bytes.
+#[cfg(not(feature = "alloc"))]
+struct Payload<'a, W: Writer> {
+	encoder: &'a mut Encoder<W>,
+	len: usize,
+	left: usize,
+	error: Option<Error>,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<'a, W: Writer> core::fmt::Write for Payload<'a, W> {
+	fn write_str(&mut self, s: &str) -> core::fmt::Result {
+		if s.len() > self.left {
+			self.error = Some(ser::Error::custom(UNSTABLE_DISPLAY));
+			return Err(core::fmt::Error);
+		}
+		match self.encoder.write_payload(s.as_bytes()) {
+			Ok(len) => {
+				self.len += len;
+				self.left -= s.len();
+				Ok(())
+			}
+			Err(error) => {
+				self.error = Some(error);
+				Err(core::fmt::Error)
+			}
+		}
+	}
+}
+
 pub struct SerializeSeq<'a, W: Writer> {
 	se: &'a mut Serializer<W>,
 	serialize_len: usize,
@@ -371,20 +355,18 @@ impl<'a, W: Writer> ser::SerializeSeq for SerializeSeq<'a, W> {
 	type Error = Error;
 
 	#[inline]
-	fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+	fn serialize_element<T>(&mut self, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
-		Ok({
-			self.serialize_len += value.serialize(&mut *self.se)?;
-		})
+		self.serialize_len += value.serialize(&mut *self.se)?;
+		Ok(())
 	}
 
 	#[inline]
 	fn end(self) -> Result<Self::Ok> {
 		if self.end_marker {
-			self.se.buffer[0] = HEADER_BREAK;
-			Ok(self.serialize_len + self.se.writer.write(&self.se.buffer[..1])?)
+			Ok(self.serialize_len + self.se.encoder.push_break()?)
 		} else {
 			Ok(self.serialize_len)
 		}
@@ -401,9 +383,9 @@ impl<'a, W: Writer> ser::SerializeTuple for SerializeTuple<'a, W> {
 	type Error = Error;
 
 	#[inline]
-	fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+	fn serialize_element<T>(&mut self, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += value.serialize(&mut *self.se)?;
 		Ok(())
@@ -425,9 +407,9 @@ impl<'a, W: Writer> ser::SerializeTupleStruct for SerializeTupleStruct<'a, W> {
 	type Error = Error;
 
 	#[inline]
-	fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += value.serialize(&mut *self.se)?;
 		Ok(())
@@ -449,9 +431,9 @@ impl<'a, W: Writer> ser::SerializeTupleVariant for SerializeTupleVariant<'a, W>
 	type Error = Error;
 
 	#[inline]
-	fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += value.serialize(&mut *self.se)?;
 		Ok(())
@@ -474,18 +456,18 @@ impl<'a, W: Writer> ser::SerializeMap for SerializeMap<'a, W> {
 	type Error = Error;
 
 	#[inline]
-	fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+	fn serialize_key<T>(&mut self, key: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += key.serialize(&mut *self.se)?;
 		Ok(())
 	}
 
 	#[inline]
-	fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+	fn serialize_value<T>(&mut self, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += value.serialize(&mut *self.se)?;
 		Ok(())
@@ -494,8 +476,7 @@ impl<'a, W: Writer> ser::SerializeMap for SerializeMap<'a, W> {
 	#[inline]
 	fn end(self) -> Result<Self::Ok> {
 		if self.end_marker {
-			self.se.buffer[0] = HEADER_BREAK;
-			Ok(self.serialize_len + self.se.writer.write(&self.se.buffer[..1])?)
+			Ok(self.serialize_len + self.se.encoder.push_break()?)
 		} else {
 			Ok(self.serialize_len)
 		}
@@ -512,9 +493,9 @@ impl<'a, W: Writer> ser::SerializeStruct for SerializeStruct<'a, W> {
 	type Error = Error;
 
 	#[inline]
-	fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += ser::Serializer::serialize_str(&mut *self.se, key)?;
 		self.serialize_len += value.serialize(&mut *self.se)?;
@@ -537,9 +518,9 @@ impl<'a, W: Writer> ser::SerializeStructVariant for SerializeStructVariant<'a, W
 	type Error = Error;
 
 	#[inline]
-	fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+	fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
 	where
-		T: ser::Serialize,
+		T: ?Sized + ser::Serialize,
 	{
 		self.serialize_len += ser::Serializer::serialize_str(&mut *self.se, key)?;
 		self.serialize_len += value.serialize(&mut *self.se)?;
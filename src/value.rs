@@ -0,0 +1,240 @@
+use crate::cbor::*;
+use crate::decode::*;
+use crate::diag::Printer;
+use crate::encode::*;
+use crate::error::*;
+use crate::read::*;
+use crate::write::*;
+use crate::Result;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A dynamically typed data item, keeping what serde cannot express
+/// such as tags, undefined and keys of any type.
+///
+/// Indefinite strings and containers are decoded as definite ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	UInt(u64),
+	/// The negative integer `-1 - n`.
+	NegInt(u64),
+	Bytes(Vec<u8>),
+	Text(String),
+	Array(Vec<Value>),
+	/// The pairs of a map, in the order of the input.
+	Map(Vec<(Value, Value)>),
+	Tag(u64, Box<Value>),
+	Bool(bool),
+	Null,
+	Undefined,
+	/// Any other simple value.
+	Simple(u8),
+	Float(f64),
+}
+
+impl Value {
+	/// Decode the single data item filling `slice`.
+	pub fn from_slice(slice: &[u8]) -> Result<Self> {
+		let mut decoder = Decoder::new(SliceReader::new(slice));
+		let value = Value::decode(&mut decoder)?;
+		decoder.end()?;
+		Ok(value)
+	}
+
+	/// Decode the next data item of `decoder`, containers and tags
+	/// nested deeper than its depth limit being rejected.
+	pub fn decode<'r, R: Reader<'r>>(decoder: &mut Decoder<R>) -> Result<Self> {
+		Ok(match decoder.header()? {
+			Header::Positive(value) => Value::UInt(value),
+			Header::Negative(value) => Value::NegInt(value),
+			Header::Bytes(Some(len)) => Value::Bytes(payload(decoder, len)?.to_vec()),
+			Header::Bytes(None) => {
+				let mut bytes = Vec::new();
+				while let Some(len) = next_chunk(decoder, MAJOR_BYTE)? {
+					bytes.extend_from_slice(payload(decoder, len)?);
+				}
+				Value::Bytes(bytes)
+			}
+			Header::Text(Some(len)) => {
+				Value::Text(core::str::from_utf8(payload(decoder, len)?)?.into())
+			}
+			Header::Text(None) => {
+				let mut text = String::new();
+				while let Some(len) = next_chunk(decoder, MAJOR_TEXT)? {
+					text.push_str(core::str::from_utf8(payload(decoder, len)?)?);
+				}
+				Value::Text(text)
+			}
+			Header::Array(len) => {
+				decoder.enter()?;
+				let mut items = Vec::new();
+				while has_next(decoder, items.len(), len)? {
+					items.push(Value::decode(decoder)?);
+				}
+				decoder.leave();
+				Value::Array(items)
+			}
+			Header::Map(len) => {
+				decoder.enter()?;
+				let mut pairs = Vec::new();
+				while has_next(decoder, pairs.len(), len)? {
+					pairs.push((Value::decode(decoder)?, Value::decode(decoder)?));
+				}
+				decoder.leave();
+				Value::Map(pairs)
+			}
+			Header::Tag(tag) => {
+				decoder.enter()?;
+				let value = Value::decode(decoder)?;
+				decoder.leave();
+				Value::Tag(tag, Box::new(value))
+			}
+			Header::Simple(SIMPLE_FALSE) => Value::Bool(false),
+			Header::Simple(SIMPLE_TRUE) => Value::Bool(true),
+			Header::Simple(SIMPLE_NULL) => Value::Null,
+			Header::Simple(SIMPLE_UNDEFINED) => Value::Undefined,
+			Header::Simple(value) => Value::Simple(value),
+			Header::Float(value) => Value::Float(value),
+			Header::Break => return Err(Error::Unexpected(HEADER_BREAK, "any other header")),
+		})
+	}
+
+	/// Encode the data item with definite lengths.
+	pub fn encode<W: Writer>(&self, encoder: &mut Encoder<W>) -> Result<usize> {
+		match self {
+			Value::UInt(value) => encoder.push_uint(*value),
+			Value::NegInt(value) => encoder.push_negint(*value),
+			Value::Bytes(value) => encoder.push_bytes(value),
+			Value::Text(value) => encoder.push_text(value),
+			Value::Array(items) => {
+				let mut len = encoder.begin_array(Some(items.len() as u64))?;
+				for item in items {
+					len += item.encode(encoder)?;
+				}
+				Ok(len)
+			}
+			Value::Map(pairs) => {
+				let mut len = encoder.begin_map(Some(pairs.len() as u64))?;
+				for (key, value) in pairs {
+					len += key.encode(encoder)?;
+					len += value.encode(encoder)?;
+				}
+				Ok(len)
+			}
+			Value::Tag(tag, value) => Ok(encoder.push_tag(*tag)? + value.encode(encoder)?),
+			Value::Bool(false) => encoder.push_simple(SIMPLE_FALSE),
+			Value::Bool(true) => encoder.push_simple(SIMPLE_TRUE),
+			Value::Null => encoder.push_simple(SIMPLE_NULL),
+			Value::Undefined => encoder.push_simple(SIMPLE_UNDEFINED),
+			Value::Simple(value) => encoder.push_simple(*value),
+			Value::Float(value) => encoder.push_float(*value),
+		}
+	}
+
+	pub fn to_vec(&self) -> Result<Vec<u8>> {
+		let mut vec = Vec::new();
+		self.encode(&mut Encoder::new(VecWriter::new(&mut vec)))?;
+		Ok(vec)
+	}
+}
+
+#[inline]
+pub(crate) fn payload<'a, 'r: 'a, R: Reader<'r>>(decoder: &'a mut Decoder<R>, len: u64) -> Result<&'a [u8]> {
+	Ok(match decoder.read_payload(len)? {
+		EitherLifetime::Current(bytes) => bytes,
+		EitherLifetime::Other(bytes) => bytes,
+	})
+}
+
+/// Print the value in diagnostic notation (RFC 8949 section 8),
+/// indented over several lines with the alternate flag `{:#}`.
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut printer = Printer::new(f.alternate());
+		printer.value(self);
+		f.write_str(&printer.into_string())
+	}
+}
+
+/// Length of the next chunk of an indefinite string of type `major`,
+/// `None` once the break is reached.
+fn next_chunk<'r, R: Reader<'r>>(decoder: &mut Decoder<R>, major: u8) -> Result<Option<u64>> {
+	match decoder.header()? {
+		Header::Break => Ok(None),
+		Header::Bytes(Some(len)) if major == MAJOR_BYTE => Ok(Some(len)),
+		Header::Text(Some(len)) if major == MAJOR_TEXT => Ok(Some(len)),
+		_ => Err(Error::Unexpected(decoder.initial(), "definite string chunk")),
+	}
+}
+
+/// Whether a container of `len` items, `None` if indefinite, has another
+/// item after the `count` first ones. The break of an indefinite container
+/// is consumed.
+pub(crate) fn has_next<'r, R: Reader<'r>>(decoder: &mut Decoder<R>, count: usize, len: Option<u64>) -> Result<bool> {
+	match len {
+		Some(len) => Ok((count as u64) < len),
+		None => match decoder.peek()? {
+			Header::Break => {
+				decoder.header()?;
+				Ok(false)
+			}
+			_ => Ok(true),
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		// {1: [_ -1, h'01', 1(undefined)], "a": (_ "b", "c"), simple(16): 1.5}
+		let bytes = [
+			0xA3, 0x01, 0x9F, 0x20, 0x41, 0x01, 0xC1, 0xF7, 0xFF, 0x61, 0x61, 0x7F, 0x61, 0x62, 0x61, 0x63, 0xFF,
+			0xF0, 0xF9, 0x3E, 0x00,
+		];
+		let value = Value::from_slice(&bytes).unwrap();
+		assert_eq!(
+			value,
+			Value::Map(alloc::vec![
+				(
+					Value::UInt(1),
+					Value::Array(alloc::vec![
+						Value::NegInt(0),
+						Value::Bytes(alloc::vec![1]),
+						Value::Tag(1, Box::new(Value::Undefined)),
+					])
+				),
+				(Value::Text("a".into()), Value::Text("bc".into())),
+				(Value::Simple(16), Value::Float(1.5)),
+			])
+		);
+		let encoded = value.to_vec().unwrap();
+		assert_eq!(Value::from_slice(&encoded).unwrap(), value);
+	}
+
+	#[test]
+	fn depth_is_limited() {
+		let mut bytes = [0x81; 100_001];
+		bytes[100_000] = 0x00;
+		assert!(matches!(Value::from_slice(&bytes), Err(Error::Limit(LimitKind::Depth))));
+		let mut bytes = [0xC1; 100_001];
+		bytes[100_000] = 0x00;
+		assert!(matches!(Value::from_slice(&bytes), Err(Error::Limit(LimitKind::Depth))));
+
+		let depth = bytes.len() - DEPTH_LIMIT - 1;
+		assert!(Value::from_slice(&bytes[depth..]).is_ok());
+		assert!(Value::from_slice(&bytes[depth - 1..]).is_err());
+	}
+
+	#[test]
+	fn malformed_items() {
+		assert!(matches!(Value::from_slice(&[0xFF]), Err(Error::Unexpected(HEADER_BREAK, _))));
+		assert!(matches!(Value::from_slice(&[0x5F, 0x61, 0x00, 0xFF]), Err(Error::Unexpected(0x61, _))));
+		assert!(matches!(Value::from_slice(&[0x82, 0x01]), Err(Error::Eof)));
+	}
+}
+
@@ -0,0 +1,265 @@
+use crate::cbor::*;
+use crate::decode::*;
+use crate::error::*;
+use crate::read::SliceReader;
+use crate::Result;
+use serde::de;
+use core::convert::TryFrom;
+
+/// A read-only cursor over one data item of a byte slice.
+///
+/// Sub-items are located by skipping their siblings, nothing is
+/// deserialized until asked for.
+#[derive(Clone, Copy, Debug)]
+pub struct CborView<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> CborView<'a> {
+	/// Create a view over the data item held by `bytes`.
+	/// The slice must hold exactly one well-formed data item.
+	pub fn new(bytes: &'a [u8]) -> Result<Self> {
+		let mut decoder = Decoder::new(SliceReader::new(bytes));
+		decoder.skip()?;
+		decoder.end()?;
+		Ok(CborView { bytes })
+	}
+
+	/// The exact bytes of the data item.
+	pub fn as_bytes(&self) -> &'a [u8] {
+		self.bytes
+	}
+
+	/// The initial byte of the data item.
+	pub fn header(&self) -> u8 {
+		self.bytes[0]
+	}
+
+	/// Look up the value of a text key in a map.
+	pub fn get(&self, key: &str) -> Result<Option<CborView<'a>>> {
+		for entry in self.entries()? {
+			let (k, v) = entry?;
+			if k.as_str().ok() == Some(key) {
+				return Ok(Some(v));
+			}
+		}
+		Ok(None)
+	}
+
+	/// The `n`-th element of an array.
+	pub fn index(&self, n: usize) -> Result<Option<CborView<'a>>> {
+		self.iter()?.nth(n).transpose()
+	}
+
+	/// The number of elements of an array or the number of pairs of a map.
+	pub fn len(&self) -> Result<usize> {
+		match self.decoder().header()? {
+			Header::Array(Some(len)) | Header::Map(Some(len)) => Ok(usize::try_from(len)?),
+			Header::Array(None) => self.iter()?.try_fold(0, |len, item| item.map(|_| len + 1)),
+			Header::Map(None) => self.entries()?.try_fold(0, |len, entry| entry.map(|_| len + 1)),
+			_ => Err(Error::Unexpected(self.header(), "array or map")),
+		}
+	}
+
+	/// Whether an array or a map is empty.
+	pub fn is_empty(&self) -> Result<bool> {
+		Ok(self.len()? == 0)
+	}
+
+	/// The content of a definite text string.
+	pub fn as_str(&self) -> Result<&'a str> {
+		let mut decoder = self.decoder();
+		match decoder.header()? {
+			Header::Text(Some(_)) => Ok(core::str::from_utf8(&self.bytes[decoder.offset()..])?),
+			_ => Err(Error::Unexpected(self.header(), "definite string")),
+		}
+	}
+
+	/// The content of a definite byte string.
+	pub fn as_slice(&self) -> Result<&'a [u8]> {
+		let mut decoder = self.decoder();
+		match decoder.header()? {
+			Header::Bytes(Some(_)) => Ok(&self.bytes[decoder.offset()..]),
+			_ => Err(Error::Unexpected(self.header(), "definite byte string")),
+		}
+	}
+
+	/// The value of an unsigned integer.
+	pub fn as_u64(&self) -> Result<u64> {
+		match self.decoder().header()? {
+			Header::Positive(value) => Ok(value),
+			_ => Err(Error::Unexpected(self.header(), "unsigned integer")),
+		}
+	}
+
+	/// Iterate over the elements of an array.
+	pub fn iter(&self) -> Result<Iter<'a>> {
+		let mut decoder = self.decoder();
+		match decoder.header()? {
+			Header::Array(remaining) => Ok(Iter {
+				bytes: self.bytes,
+				decoder,
+				remaining,
+			}),
+			_ => Err(Error::Unexpected(self.header(), "array")),
+		}
+	}
+
+	/// Iterate over the key/value pairs of a map.
+	pub fn entries(&self) -> Result<Entries<'a>> {
+		let mut decoder = self.decoder();
+		match decoder.header()? {
+			Header::Map(remaining) => Ok(Entries {
+				iter: Iter {
+					bytes: self.bytes,
+					decoder,
+					remaining: match remaining {
+						Some(len) => Some(len.checked_mul(2).ok_or(Error::Limit(LimitKind::Index))?),
+						None => None,
+					},
+				},
+			}),
+			_ => Err(Error::Unexpected(self.header(), "map")),
+		}
+	}
+
+	/// Deserialize the data item.
+	pub fn decode<T>(&self) -> Result<T>
+	where
+		T: de::Deserialize<'a>,
+	{
+		crate::from_slice(self.bytes)
+	}
+
+	#[inline]
+	fn decoder(&self) -> Decoder<SliceReader<'a>> {
+		Decoder::new(SliceReader::new(self.bytes))
+	}
+}
+
+/// An iterator over the elements of an array view.
+pub struct Iter<'a> {
+	bytes: &'a [u8],
+	decoder: Decoder<SliceReader<'a>>,
+	remaining: Option<u64>,
+}
+
+impl<'a> Iter<'a> {
+	fn next_view(&mut self) -> Result<Option<CborView<'a>>> {
+		match self.remaining {
+			Some(0) => return Ok(None),
+			Some(ref mut remaining) => *remaining -= 1,
+			None => {
+				if self.decoder.skip_break(None)? {
+					self.remaining = Some(0);
+					return Ok(None);
+				}
+			}
+		}
+		let start = self.decoder.offset();
+		self.decoder.skip()?;
+		Ok(Some(CborView {
+			bytes: &self.bytes[start..self.decoder.offset()],
+		}))
+	}
+}
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = Result<CborView<'a>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let view = self.next_view();
+		if view.is_err() {
+			self.remaining = Some(0);
+		}
+		view.transpose()
+	}
+}
+
+/// An iterator over the key/value pairs of a map view.
+pub struct Entries<'a> {
+	iter: Iter<'a>,
+}
+
+impl<'a> Iterator for Entries<'a> {
+	type Item = Result<(CborView<'a>, CborView<'a>)>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let key = match self.iter.next()? {
+			Ok(key) => key,
+			Err(error) => return Some(Err(error)),
+		};
+		match self.iter.next() {
+			Some(Ok(value)) => Some(Ok((key, value))),
+			Some(Err(error)) => Some(Err(error)),
+			None => Some(Err(Error::Unexpected(HEADER_BREAK, "map value"))),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// {"a": [1, h'02', "three"], "b": {_ 1: 2}, 3: [_ 4, 5]}
+	const ITEM: [u8; 24] = [
+		0xA3, 0x61, 0x61, 0x83, 0x01, 0x41, 0x02, 0x65, 0x74, 0x68, 0x72, 0x65, 0x65, 0x61, 0x62, 0xBF, 0x01,
+		0x02, 0xFF, 0x03, 0x9F, 0x04, 0x05, 0xFF,
+	];
+
+	#[test]
+	fn new_takes_exactly_one_item() {
+		assert!(CborView::new(&ITEM).is_ok());
+		assert!(matches!(CborView::new(&ITEM[..23]), Err(Error::Eof)));
+		assert!(matches!(CborView::new(&[0x01, 0x02]), Err(Error::TrailingData(1))));
+		assert!(matches!(CborView::new(&[]), Err(Error::Eof)));
+	}
+
+	#[test]
+	fn lookup() {
+		let view = CborView::new(&ITEM).unwrap();
+		assert_eq!(view.header(), 0xA3);
+		assert_eq!(view.as_bytes(), &ITEM[..]);
+		assert_eq!(view.len().unwrap(), 3);
+
+		let a = view.get("a").unwrap().unwrap();
+		assert_eq!(a.as_bytes(), &ITEM[3..13]);
+		assert_eq!(a.index(0).unwrap().unwrap().as_u64().unwrap(), 1);
+		assert_eq!(a.index(1).unwrap().unwrap().as_slice().unwrap(), &[0x02]);
+		assert_eq!(a.index(2).unwrap().unwrap().as_str().unwrap(), "three");
+		assert!(a.index(3).unwrap().is_none());
+
+		let b = view.get("b").unwrap().unwrap();
+		assert_eq!(b.len().unwrap(), 1);
+		let (key, value) = b.entries().unwrap().next().unwrap().unwrap();
+		assert_eq!((key.as_u64().unwrap(), value.as_u64().unwrap()), (1, 2));
+
+		assert!(view.get("c").unwrap().is_none());
+		let (_, indefinite) = view.entries().unwrap().nth(2).unwrap().unwrap();
+		assert_eq!(indefinite.len().unwrap(), 2);
+		assert!(!indefinite.is_empty().unwrap());
+		assert_eq!(indefinite.index(1).unwrap().unwrap().as_bytes(), &[0x05]);
+	}
+
+	#[test]
+	fn wrong_types() {
+		let view = CborView::new(&ITEM).unwrap();
+		assert!(matches!(view.iter(), Err(Error::Unexpected(0xA3, _))));
+		assert!(matches!(view.as_u64(), Err(Error::Unexpected(0xA3, _))));
+		let a = view.get("a").unwrap().unwrap();
+		assert!(matches!(a.get("a"), Err(Error::Unexpected(0x83, _))));
+		assert!(matches!(a.index(0).unwrap().unwrap().len(), Err(Error::Unexpected(0x01, _))));
+		assert!(matches!(CborView::new(&[0x7F, 0xFF]).unwrap().as_str(), Err(Error::Unexpected(0x7F, _))));
+	}
+
+	#[test]
+	fn decode_borrows() {
+		let view = CborView::new(&ITEM).unwrap();
+		let a = view.get("a").unwrap().unwrap();
+		let text: &str = a.index(2).unwrap().unwrap().decode().unwrap();
+		assert_eq!(text, "three");
+		assert_eq!(text.as_ptr(), ITEM[8..].as_ptr());
+		let (first, _, _): (u8, &[u8], &str) = a.decode().unwrap();
+		assert_eq!(first, 1);
+	}
+}
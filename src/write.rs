@@ -1,5 +1,8 @@
 use crate::error::*;
 use crate::Result;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::io;
 
 pub trait Writer {
@@ -13,10 +16,7 @@ pub struct SliceWriter<'w> {
 
 impl<'w> SliceWriter<'w> {
 	pub fn new(slice: &'w mut [u8]) -> Self {
-		SliceWriter {
-			slice: slice,
-			index: 0,
-		}
+		SliceWriter { slice, index: 0 }
 	}
 
 	#[inline]
@@ -26,10 +26,10 @@ impl<'w> SliceWriter<'w> {
 				if end <= self.slice.len() {
 					Ok(end)
 				} else {
-					Err(Error::Message("Try to write after the end of the slice."))
+					Err(Error::Limit(LimitKind::WriteSlice))
 				}
 			}
-			None => Err(Error::Message("Write into an index that exeed usize::max")),
+			None => Err(Error::Limit(LimitKind::Index)),
 		}
 	}
 }
@@ -50,19 +50,186 @@ impl<'w> Writer for SliceWriter<'w> {
 	}
 }
 
+/// A writer appending to a `Vec<u8>`.
+#[cfg(feature = "alloc")]
+pub struct VecWriter<'w> {
+	vec: &'w mut Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'w> VecWriter<'w> {
+	pub fn new(vec: &'w mut Vec<u8>) -> Self {
+		VecWriter { vec }
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'w> Writer for VecWriter<'w> {
+	#[inline]
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.vec.extend_from_slice(bytes);
+		Ok(bytes.len())
+	}
+}
+
+/// A writer appending to a `heapless::Vec<u8, N>`, failing once it is full.
+#[cfg(feature = "heapless")]
+pub struct HeaplessWriter<'w, const N: usize> {
+	vec: &'w mut heapless::Vec<u8, N>,
+}
+
+#[cfg(feature = "heapless")]
+impl<'w, const N: usize> HeaplessWriter<'w, N> {
+	pub fn new(vec: &'w mut heapless::Vec<u8, N>) -> Self {
+		HeaplessWriter { vec }
+	}
+}
+
+#[cfg(feature = "heapless")]
+impl<'w, const N: usize> Writer for HeaplessWriter<'w, N> {
+	#[inline]
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.vec
+			.extend_from_slice(bytes)
+			.map_err(|_| Error::Limit(LimitKind::WriteSlice))?;
+		Ok(bytes.len())
+	}
+}
+
+/// A writer discarding the bytes, only counting them.
+#[derive(Default)]
+pub struct SizeWriter {
+	size: usize,
+}
+
+impl SizeWriter {
+	pub fn new() -> Self {
+		SizeWriter { size: 0 }
+	}
+
+	/// Number of bytes written so far.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+}
+
+impl Writer for SizeWriter {
+	#[inline]
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.size = self
+			.size
+			.checked_add(bytes.len())
+			.ok_or(Error::Limit(LimitKind::Index))?;
+		Ok(bytes.len())
+	}
+}
+
+#[cfg(feature = "std")]
 pub struct IoWriter<W: io::Write> {
 	writer: W,
 }
 
+#[cfg(feature = "std")]
 impl<W: io::Write> IoWriter<W> {
 	pub fn new(io: W) -> Self {
 		IoWriter { writer: io }
 	}
 }
 
+#[cfg(feature = "std")]
 impl<W: io::Write> Writer for IoWriter<W> {
 	#[inline]
 	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
-		Ok(self.writer.write(bytes)?)
+		self.writer.write_all(bytes)?;
+		Ok(bytes.len())
+	}
+}
+
+/// A writer appending to a `bytes::BufMut`, such as a `BytesMut`.
+#[cfg(feature = "bytes")]
+pub struct BufMutWriter<B: bytes::BufMut> {
+	buf: B,
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::BufMut> BufMutWriter<B> {
+	pub fn new(buf: B) -> Self {
+		BufMutWriter { buf }
+	}
+
+	pub fn into_inner(self) -> B {
+		self.buf
+	}
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::BufMut> Writer for BufMutWriter<B> {
+	#[inline]
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		if self.buf.remaining_mut() < bytes.len() {
+			return Err(Error::Limit(LimitKind::WriteSlice));
+		}
+		self.buf.put_slice(bytes);
+		Ok(bytes.len())
+	}
+}
+
+/// A writer over an `embedded_io::Write` stream.
+#[cfg(feature = "embedded-io")]
+pub struct EmbeddedWriter<W: embedded_io::Write> {
+	writer: W,
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> EmbeddedWriter<W> {
+	pub fn new(io: W) -> Self {
+		EmbeddedWriter { writer: io }
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> Writer for EmbeddedWriter<W> {
+	#[inline]
+	fn write(&mut self, bytes: &[u8]) -> Result<usize> {
+		self.writer.write_all(bytes).map_err(Error::embedded_io)?;
+		Ok(bytes.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slice_writer_is_bounded() {
+		let mut slice = [0u8; 4];
+		let mut writer = SliceWriter::new(&mut slice);
+		assert_eq!(writer.write(&[1, 2, 3]).unwrap(), 3);
+		assert!(matches!(writer.write(&[4, 5]), Err(Error::Limit(LimitKind::WriteSlice))));
+		assert_eq!(writer.write(&[4]).unwrap(), 1);
+		assert_eq!(slice, [1, 2, 3, 4]);
+	}
+
+	#[cfg(feature = "alloc")]
+	#[test]
+	fn vec_writer_appends() {
+		let mut vec = alloc::vec![0xAA];
+		let mut writer = VecWriter::new(&mut vec);
+		assert_eq!(writer.write(&[1, 2]).unwrap(), 2);
+		assert_eq!(writer.write(&[]).unwrap(), 0);
+		assert_eq!(writer.write(&[3]).unwrap(), 1);
+		assert_eq!(vec, [0xAA, 1, 2, 3]);
+	}
+
+	#[test]
+	fn size_writer_counts() {
+		let mut writer = SizeWriter::new();
+		writer.write(&[1, 2, 3]).unwrap();
+		writer.write(&[4]).unwrap();
+		assert_eq!(writer.size(), 4);
 	}
 }
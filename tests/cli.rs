@@ -0,0 +1,106 @@
+//! Round trips through the `cbor` binary.
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], input: &[u8]) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_cbor"))
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(input).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+fn to_json(args: &[&str], input: &[u8]) -> String {
+	let mut args = args.to_vec();
+	args.insert(0, "to-json");
+	let output = run(&args, input);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout).unwrap()
+}
+
+fn from_json(args: &[&str], input: &str) -> Vec<u8> {
+	let mut args = args.to_vec();
+	args.insert(0, "from-json");
+	let output = run(&args, input.as_bytes());
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	output.stdout
+}
+
+#[test]
+fn json_round_trip() {
+	let json = r#"{"b":[1,-2,1.5,"x",true,null],"a":{}}"#;
+	let cbor = from_json(&[], json);
+	assert_eq!(cbor[..3], [0xA2, 0x61, b'b']);
+	assert_eq!(to_json(&[], &cbor), format!("{}\n", json));
+	assert_eq!(to_json(&["--pretty"], &[0x81, 0x01]), "[\n  1\n]\n");
+}
+
+#[test]
+fn byte_strings() {
+	assert_eq!(to_json(&[], &[0x44, 0x01, 0x02, 0x03, 0xFB]), "\"AQID-w\"\n");
+	// Expected conversions of tags 21 to 23.
+	assert_eq!(to_json(&[], &[0xD6, 0x44, 0x01, 0x02, 0x03, 0xFB]), "\"AQID+w==\"\n");
+	assert_eq!(to_json(&[], &[0xD7, 0x42, 0xAB, 0x01]), "\"ab01\"\n");
+	assert_eq!(to_json(&[], &[0xD6, 0xD5, 0x41, 0xFB]), "\"-w\"\n");
+	assert_eq!(to_json(&[], &[0xD7, 0x81, 0x41, 0xFB]), "[\"fb\"]\n");
+}
+
+#[test]
+fn tags() {
+	// Bignums, then tags dropped keeping their content.
+	assert_eq!(to_json(&[], &[0xC2, 0x42, 0x01, 0x00]), "\"AQA\"\n");
+	assert_eq!(to_json(&[], &[0xC3, 0x42, 0x01, 0x00]), "\"~AQA\"\n");
+	assert_eq!(to_json(&[], &[0xC1, 0x1A, 0x5F, 0x5E, 0x10, 0x00]), "1600000000\n");
+	assert_eq!(to_json(&[], &[0xD8, 0x20, 0x61, 0x78]), "\"x\"\n");
+}
+
+#[test]
+fn simple_values() {
+	assert_eq!(to_json(&[], &[0x84, 0xF7, 0xF0, 0xF6, 0xF4]), "[null,null,null,false]\n");
+	assert_eq!(to_json(&[], &[0x83, 0xF9, 0x7C, 0x00, 0xFB, 0x7F, 0xF8, 0, 0, 0, 0, 0, 0, 0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]), "[null,null,-9223372036854775808]\n");
+}
+
+#[test]
+fn non_string_keys() {
+	// {1: "a", [2]: "b", h'03': "c", "1": "d"}
+	let cbor = [0xA4, 0x01, 0x61, b'a', 0x81, 0x02, 0x61, b'b', 0x41, 0x03, 0x61, b'c', 0x61, b'1', 0x61, b'd'];
+	assert_eq!(to_json(&[], &cbor), "{\"1\":\"d\",\"[2]\":\"b\",\"\\\"Aw\\\"\":\"c\"}\n");
+}
+
+#[test]
+fn sequences() {
+	let cbor = from_json(&["--sequence"], "1 [2]\n{\"a\": 3}");
+	assert_eq!(cbor, [0x01, 0x81, 0x02, 0xA1, 0x61, b'a', 0x03]);
+	assert_eq!(to_json(&["--sequence"], &cbor), "1\n[2]\n{\"a\":3}\n");
+	assert_eq!(to_json(&["--sequence"], &[]), "");
+
+	// Without `--sequence`, a single item is expected.
+	let output = run(&["to-json"], &cbor);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(String::from_utf8_lossy(&output.stderr), "cbor: at byte 1: Trailing data at offset 1\n");
+	let output = run(&["to-json", "--sequence"], &[0x01, 0x82, 0x01]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(output.stdout, b"1\n");
+}
+
+#[test]
+fn deep_input_is_rejected() {
+	let output = run(&["to-json"], &[0x81; 1_000_000]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(String::from_utf8_lossy(&output.stderr), "cbor: at byte 129: Containers nested too deeply.\n");
+	let output = run(&["to-json"], &[0xC1; 1_000_000]);
+	assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn usage() {
+	assert_eq!(run(&[], b"").status.code(), Some(2));
+	assert_eq!(run(&["from-json", "--pretty"], b"").status.code(), Some(2));
+	assert!(run(&["--help"], b"").status.success());
+}
@@ -0,0 +1,110 @@
+//! Decoding without a heap, under a global allocator that panics on use.
+#![cfg(all(feature = "heapless", not(feature = "alloc")))]
+
+use orandja_cbor::error::{Error, MESSAGE_CAPACITY};
+use serde_derive::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+	static ARMED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Panics on any allocation made by a thread running `no_alloc`.
+struct PanickingAllocator;
+
+unsafe impl GlobalAlloc for PanickingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		if ARMED.with(|armed| armed.replace(false)) {
+			panic!("allocation of {} bytes", layout.size());
+		}
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: PanickingAllocator = PanickingAllocator;
+
+/// Run `f` with allocations forbidden on the current thread.
+fn no_alloc<T>(f: impl FnOnce() -> T) -> T {
+	ARMED.with(|armed| armed.set(true));
+	let value = f();
+	ARMED.with(|armed| armed.set(false));
+	value
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Reading {
+	sensor: heapless::String<8>,
+	values: heapless::Vec<i16, 4>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct Label<'a> {
+	name: &'a str,
+	unit: Option<&'a str>,
+}
+
+/// {"sensor": "temp", "values": [-1, 20, 300]}
+const READING: [u8; 27] = [
+	0xA2, 0x66, b's', b'e', b'n', b's', b'o', b'r', 0x64, b't', b'e', b'm', b'p', 0x66, b'v', b'a', b'l',
+	b'u', b'e', b's', 0x83, 0x20, 0x14, 0x19, 0x01, 0x2C, 0xF6,
+];
+
+#[test]
+fn heapless_collections() {
+	let reading = no_alloc(|| orandja_cbor::from_slice::<Reading>(&READING[..26]).unwrap());
+	assert_eq!(reading.sensor, "temp");
+	assert_eq!(reading.values, [-1, 20, 300]);
+}
+
+#[test]
+fn heapless_collections_overflow() {
+	// Five values do not fit in a `heapless::Vec<i16, 4>`.
+	let mut bytes = [0x01; 28];
+	bytes[..26].copy_from_slice(&READING[..26]);
+	bytes[20] = 0x85;
+	let result = no_alloc(|| orandja_cbor::from_slice::<Reading>(&bytes).map(|_| ()));
+	assert!(matches!(result, Err(Error::Serde(..))));
+}
+
+#[test]
+fn borrowed_str() {
+	// {"name": "volt", "unit": "V"}
+	let bytes = [
+		0xA2, 0x64, b'n', b'a', b'm', b'e', 0x64, b'v', b'o', b'l', b't', 0x64, b'u', b'n', b'i', b't', 0x61,
+		b'V',
+	];
+	let label = no_alloc(|| orandja_cbor::from_slice::<Label>(&bytes).unwrap());
+	assert_eq!(label, Label { name: "volt", unit: Some("V") });
+	assert_eq!(label.name.as_ptr(), bytes[7..].as_ptr());
+}
+
+#[test]
+fn to_heapless_vec_round_trip() {
+	let reading = orandja_cbor::from_slice::<Reading>(&READING[..26]).unwrap();
+	let encoded = no_alloc(|| orandja_cbor::to_heapless_vec::<_, 32>(&reading).unwrap());
+	assert_eq!(encoded, READING[..26]);
+	let result = no_alloc(|| orandja_cbor::to_heapless_vec::<_, 16>(&reading).map(|_| ()));
+	assert!(result.is_err());
+}
+
+#[test]
+fn messages_are_truncated() {
+	let long = "é".repeat(MESSAGE_CAPACITY);
+	let error = no_alloc(|| <Error as serde::de::Error>::custom(&long));
+	match error {
+		Error::Serde(_, message) => {
+			// Truncated on a character boundary.
+			assert_eq!(message.as_str().len(), MESSAGE_CAPACITY);
+			assert!(long.starts_with(message.as_str()));
+		}
+		_ => panic!("not a serde error"),
+	}
+	let error = no_alloc(|| <Error as serde::ser::Error>::custom("short"));
+	assert!(matches!(error, Error::Serde(_, ref message) if message.as_str() == "short"));
+}